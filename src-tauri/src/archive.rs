@@ -0,0 +1,430 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context};
+use image::ImageFormat;
+use zip::write::SimpleFileOptions;
+use zip::{DateTime, ZipArchive, ZipWriter};
+
+use crate::pdf;
+use crate::types::{ArchiveCompression, ArchiveFormat, ComicInfo, ComicPageInfo, ComicPages};
+use crate::utils::natural_cmp;
+
+pub const COMIC_INFO_FILENAME: &str = "ComicInfo.xml";
+/// epub中正文图片的路径前缀，封面图片不以此开头
+const EPUB_PAGE_PREFIX: &str = "OEBPS/images/p";
+
+/// `ImageIndex` 中记录的图片尺寸，为0表示未知
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PageSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// 名为 `name` 的章节或单行本以 `archive_format` 格式保存在 `dir` 中时的路径
+///
+/// 不使用 `with_extension`，因为名称中的 `.` 之后的部分会被当作扩展名替换掉，例如 `第1.5话` 会变成 `第1`
+pub fn get_archive_path(dir: &Path, name: &str, archive_format: &ArchiveFormat) -> PathBuf {
+    match archive_format {
+        ArchiveFormat::Image => dir.join(name),
+        _ => dir.join(format!("{name}.{}", archive_format.extension())),
+    }
+}
+
+/// 将 `temp_download_dir` 原子地替换为 `download_dir`
+///
+/// 替换前会将目录中的文件落盘，已存在的 `download_dir` 会先被重命名为备份，替换成功后才删除备份，
+/// 因此任何时刻崩溃都不会留下不完整的 `download_dir`
+pub fn save_image_dir(temp_download_dir: &Path, download_dir: &Path) -> anyhow::Result<()> {
+    sync_dir_files(temp_download_dir)?;
+
+    let backup_dir = get_temp_path(download_dir, "旧");
+    if download_dir.exists() {
+        if backup_dir.exists() {
+            std::fs::remove_dir_all(&backup_dir).context(format!("删除 {backup_dir:?} 失败"))?;
+        }
+        std::fs::rename(download_dir, &backup_dir)
+            .context(format!("将 {download_dir:?} 重命名为 {backup_dir:?} 失败"))?;
+    }
+    std::fs::rename(temp_download_dir, download_dir).context(format!(
+        "将 {temp_download_dir:?} 重命名为 {download_dir:?} 失败"
+    ))?;
+    sync_parent_dir(download_dir)?;
+
+    if backup_dir.exists() {
+        std::fs::remove_dir_all(&backup_dir).context(format!("删除 {backup_dir:?} 失败"))?;
+    }
+    Ok(())
+}
+
+/// 将 `comic_info` 和 `temp_download_dir` 中的图片打包为 `zip_path`，打包成功后删除 `temp_download_dir`
+///
+/// 通过 `write_atomically` 写入，崩溃或磁盘空间不足时不会留下被当作已下载的不完整压缩包
+pub fn save_zip(
+    temp_download_dir: &Path,
+    comic_info: &ComicInfo,
+    zip_path: &Path,
+    compression: &ArchiveCompression,
+    compression_level: u32,
+    page_sizes: &[PageSize],
+) -> anyhow::Result<()> {
+    let comic_info = comic_info_with_pages(comic_info, temp_download_dir, page_sizes)?;
+    let comic_info_xml = to_comic_info_xml(&comic_info)?;
+
+    let options = zip_file_options(compression, compression_level);
+    write_atomically(zip_path, |temp_zip_path| {
+        write_zip(temp_download_dir, &comic_info_xml, temp_zip_path, options)
+    })?;
+
+    std::fs::remove_dir_all(temp_download_dir)
+        .context(format!("删除 {temp_download_dir:?} 失败"))?;
+    Ok(())
+}
+
+/// 用 `src_dir` 中的图片填写 `comic_info` 的 `Pages`，图片尺寸优先使用 `page_sizes` 中对应的尺寸
+///
+/// 页的顺序与 `get_all_page_paths` 一致，也就是图片在压缩包中的顺序
+pub fn comic_info_with_pages(
+    comic_info: &ComicInfo,
+    src_dir: &Path,
+    page_sizes: &[PageSize],
+) -> anyhow::Result<ComicInfo> {
+    let mut pages = Vec::new();
+    for (i, page_path) in get_all_page_paths(src_dir)?.iter().enumerate() {
+        let image_size = std::fs::metadata(page_path)
+            .context(format!("获取 {page_path:?} 的元数据失败"))?
+            .len();
+        let mut page_size = page_sizes.get(i).copied().unwrap_or_default();
+        if page_size.width == 0 || page_size.height == 0 {
+            // 尺寸只是附加信息，读取失败时保留为0
+            let (width, height) = image::image_dimensions(page_path).unwrap_or_default();
+            page_size = PageSize { width, height };
+        }
+        pages.push(ComicPageInfo {
+            image: u32::try_from(i).unwrap_or(u32::MAX),
+            image_width: page_size.width,
+            image_height: page_size.height,
+            image_size,
+        });
+    }
+    Ok(ComicInfo {
+        pages: Some(ComicPages { pages }),
+        ..comic_info.clone()
+    })
+}
+
+pub fn to_comic_info_xml(comic_info: &ComicInfo) -> anyhow::Result<String> {
+    yaserde::ser::to_string(comic_info)
+        .map_err(|err_msg| anyhow!("序列化 {COMIC_INFO_FILENAME} 失败: {err_msg}"))
+}
+
+/// 读取章节或单行本中的 `ComicInfo.xml`，pdf和epub中没有 `ComicInfo.xml`，此时返回 `None`
+pub fn read_comic_info(
+    archive_path: &Path,
+    archive_format: &ArchiveFormat,
+) -> anyhow::Result<Option<ComicInfo>> {
+    let comic_info_xml = match archive_format {
+        ArchiveFormat::Image => {
+            let comic_info_path = archive_path.join(COMIC_INFO_FILENAME);
+            if !comic_info_path.exists() {
+                return Ok(None);
+            }
+            std::fs::read_to_string(&comic_info_path)
+                .context(format!("读取 {comic_info_path:?} 失败"))?
+        }
+        ArchiveFormat::Zip | ArchiveFormat::Cbz => {
+            let zip_file =
+                File::open(archive_path).context(format!("打开 {archive_path:?} 失败"))?;
+            let mut zip_archive =
+                ZipArchive::new(zip_file).context(format!("读取 {archive_path:?} 失败"))?;
+            let Ok(mut entry) = zip_archive.by_name(COMIC_INFO_FILENAME) else {
+                return Ok(None);
+            };
+            let mut comic_info_xml = String::new();
+            entry.read_to_string(&mut comic_info_xml).context(format!(
+                "读取 {archive_path:?} 中的 {COMIC_INFO_FILENAME} 失败"
+            ))?;
+            comic_info_xml
+        }
+        ArchiveFormat::Pdf | ArchiveFormat::Epub => return Ok(None),
+    };
+    let comic_info = yaserde::de::from_str(&comic_info_xml).map_err(|err_msg| {
+        anyhow!("解析 {archive_path:?} 中的 {COMIC_INFO_FILENAME} 失败: {err_msg}")
+    })?;
+    Ok(Some(comic_info))
+}
+
+/// 压缩包中每个条目的选项，使用固定的修改时间和权限，保证相同的内容总是生成完全相同的压缩包
+pub fn zip_file_options(
+    compression: &ArchiveCompression,
+    compression_level: u32,
+) -> SimpleFileOptions {
+    // 不压缩时不能指定压缩等级，Deflate只支持1到9的压缩等级，旧配置中保存的0等超出范围的值会被修正
+    let compression_level = match compression {
+        ArchiveCompression::Stored => None,
+        ArchiveCompression::Deflate => Some(i64::from(compression_level.clamp(1, 9))),
+    };
+    SimpleFileOptions::default()
+        .compression_method(compression.compression_method())
+        .compression_level(compression_level)
+        .last_modified_time(DateTime::default())
+        .unix_permissions(0o644)
+}
+
+/// 将 `comic_info_xml` 和 `src_dir` 中的图片写入 `zip_path`
+///
+/// `ComicInfo.xml` 固定为第一个条目，图片按文件名自然排序，子目录(单行本中的章节)中的图片放在同名目录下
+fn write_zip(
+    src_dir: &Path,
+    comic_info_xml: &str,
+    zip_path: &Path,
+    options: SimpleFileOptions,
+) -> anyhow::Result<()> {
+    let zip_file = File::create(zip_path).context(format!("创建 {zip_path:?} 失败"))?;
+    let mut zip_writer = ZipWriter::new(zip_file);
+
+    zip_writer
+        .start_file(COMIC_INFO_FILENAME, options)
+        .context(format!("在 {zip_path:?} 创建 {COMIC_INFO_FILENAME} 失败"))?;
+    zip_writer
+        .write_all(comic_info_xml.as_bytes())
+        .context(format!("将 {COMIC_INFO_FILENAME} 写入 {zip_path:?} 失败"))?;
+
+    write_dir_to_zip(&mut zip_writer, src_dir, "", zip_path, options)?;
+
+    zip_writer
+        .finish()
+        .context(format!("关闭 {zip_path:?} 失败"))?;
+    Ok(())
+}
+
+/// 将 `dir` 中的图片写入压缩包中的 `prefix` 目录，然后按目录名自然排序依次写入子目录
+fn write_dir_to_zip(
+    zip_writer: &mut ZipWriter<File>,
+    dir: &Path,
+    prefix: &str,
+    zip_path: &Path,
+    options: SimpleFileOptions,
+) -> anyhow::Result<()> {
+    for path in get_sorted_page_paths(dir)? {
+        let Some(filename) = path.file_name().map(|name| name.to_string_lossy()) else {
+            continue;
+        };
+        let entry_name = format!("{prefix}{filename}");
+
+        zip_writer
+            .start_file(&entry_name, options)
+            .context(format!("在 {zip_path:?} 创建 {entry_name:?} 失败"))?;
+
+        let mut file = File::open(&path).context(format!("打开 {path:?} 失败"))?;
+
+        std::io::copy(&mut file, zip_writer)
+            .context(format!("将 {path:?} 写入 {zip_path:?} 失败"))?;
+    }
+
+    for sub_dir in get_sorted_sub_dirs(dir)? {
+        let Some(dir_name) = sub_dir.file_name().map(|name| name.to_string_lossy()) else {
+            continue;
+        };
+        let sub_prefix = format!("{prefix}{dir_name}/");
+        write_dir_to_zip(zip_writer, &sub_dir, &sub_prefix, zip_path, options)?;
+    }
+    Ok(())
+}
+
+/// 获取 `dir` 中按文件名自然排序的图片路径，不包括 `ComicInfo.xml`
+pub fn get_sorted_page_paths(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut page_paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .context(format!("读取目录 {dir:?} 失败"))?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            path.file_name()
+                .is_some_and(|name| name != COMIC_INFO_FILENAME)
+        })
+        .collect();
+    page_paths.sort_by(|a, b| {
+        let a = a.file_name().unwrap_or_default().to_string_lossy();
+        let b = b.file_name().unwrap_or_default().to_string_lossy();
+        natural_cmp(&a, &b)
+    });
+    Ok(page_paths)
+}
+
+/// 将 `archive_path` 中的图片按页的顺序提取到 `dest_dir`，图片依次命名为 `001.jpg`、`002.jpg`...
+///
+/// 单行本中的图片也会按章节顺序提取到 `dest_dir`，不保留章节目录
+pub fn extract_pages(
+    archive_path: &Path,
+    archive_format: &ArchiveFormat,
+    dest_dir: &Path,
+) -> anyhow::Result<()> {
+    match archive_format {
+        ArchiveFormat::Image => {
+            for (i, page_path) in get_all_page_paths(archive_path)?.iter().enumerate() {
+                let ext = page_path.extension().unwrap_or_default().to_string_lossy();
+                let dest_path = dest_dir.join(format!("{:03}.{ext}", i + 1));
+                std::fs::copy(page_path, &dest_path)
+                    .context(format!("将 {page_path:?} 复制到 {dest_path:?} 失败"))?;
+            }
+        }
+        ArchiveFormat::Zip | ArchiveFormat::Cbz => {
+            extract_zip_pages(archive_path, dest_dir, |_| true)?;
+        }
+        // 只提取正文的图片，不提取封面
+        ArchiveFormat::Epub => extract_zip_pages(archive_path, dest_dir, |entry_name| {
+            entry_name.starts_with(EPUB_PAGE_PREFIX)
+        })?,
+        ArchiveFormat::Pdf => {
+            pdf::extract_pages(archive_path, dest_dir)?;
+        }
+    }
+    Ok(())
+}
+
+/// 按条目名的自然顺序将 `zip_path` 中满足 `filter` 的图片提取到 `dest_dir`
+fn extract_zip_pages(
+    zip_path: &Path,
+    dest_dir: &Path,
+    filter: impl Fn(&str) -> bool,
+) -> anyhow::Result<()> {
+    let zip_file = File::open(zip_path).context(format!("打开 {zip_path:?} 失败"))?;
+    let mut zip_archive = ZipArchive::new(zip_file).context(format!("读取 {zip_path:?} 失败"))?;
+    let entry_names = get_zip_page_names(&zip_archive, filter);
+
+    for (i, entry_name) in entry_names.iter().enumerate() {
+        let ext = Path::new(entry_name)
+            .extension()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let dest_path = dest_dir.join(format!("{:03}.{ext}", i + 1));
+        let mut entry = zip_archive
+            .by_name(entry_name)
+            .context(format!("读取 {zip_path:?} 中的 {entry_name} 失败"))?;
+        let mut dest_file = File::create(&dest_path).context(format!("创建 {dest_path:?} 失败"))?;
+        std::io::copy(&mut entry, &mut dest_file).context(format!(
+            "将 {zip_path:?} 中的 {entry_name} 解压到 {dest_path:?} 失败"
+        ))?;
+    }
+    Ok(())
+}
+
+/// 按条目名的自然顺序获取 `zip_archive` 中满足 `filter` 的图片条目名
+pub fn get_zip_page_names(
+    zip_archive: &ZipArchive<File>,
+    filter: impl Fn(&str) -> bool,
+) -> Vec<String> {
+    let mut entry_names: Vec<String> = zip_archive
+        .file_names()
+        .filter(|name| !name.ends_with('/') && filter(name))
+        .filter(|name| ImageFormat::from_path(name).is_ok())
+        .map(ToString::to_string)
+        .collect();
+    entry_names.sort_by(|a, b| natural_cmp(a, b));
+    entry_names
+}
+
+/// 统计章节或单行本中的图片数量，与 `extract_pages` 提取的图片一致
+pub fn count_pages(archive_path: &Path, archive_format: &ArchiveFormat) -> anyhow::Result<usize> {
+    let is_page: fn(&str) -> bool = match archive_format {
+        ArchiveFormat::Image => return Ok(get_all_page_paths(archive_path)?.len()),
+        ArchiveFormat::Pdf => return pdf::count_pages(archive_path),
+        ArchiveFormat::Zip | ArchiveFormat::Cbz => |_| true,
+        ArchiveFormat::Epub => |entry_name| entry_name.starts_with(EPUB_PAGE_PREFIX),
+    };
+    let zip_file = File::open(archive_path).context(format!("打开 {archive_path:?} 失败"))?;
+    let zip_archive = ZipArchive::new(zip_file).context(format!("读取 {archive_path:?} 失败"))?;
+    Ok(get_zip_page_names(&zip_archive, is_page).len())
+}
+
+/// 获取 `dir` 及其子目录中的所有图片路径，先是 `dir` 中的图片，然后按目录名自然排序依次是每个子目录中的图片
+pub fn get_all_page_paths(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut page_paths = get_sorted_page_paths(dir)?;
+    for sub_dir in get_sorted_sub_dirs(dir)? {
+        page_paths.extend(get_all_page_paths(&sub_dir)?);
+    }
+    Ok(page_paths)
+}
+
+/// 获取 `dir` 中按目录名自然排序的子目录路径
+pub fn get_sorted_sub_dirs(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut sub_dirs: Vec<PathBuf> = std::fs::read_dir(dir)
+        .context(format!("读取目录 {dir:?} 失败"))?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    sub_dirs.sort_by(|a, b| {
+        let a = a.file_name().unwrap_or_default().to_string_lossy();
+        let b = b.file_name().unwrap_or_default().to_string_lossy();
+        natural_cmp(&a, &b)
+    });
+    Ok(sub_dirs)
+}
+
+/// 先通过 `write` 写入与 `path` 同目录的临时文件，落盘后再重命名为 `path`，写入失败时删除临时文件
+pub fn write_atomically(
+    path: &Path,
+    write: impl FnOnce(&Path) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let temp_path = get_temp_path(path, "写入中");
+    if let Err(err) = write(&temp_path).and_then(|()| sync_file(&temp_path)) {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(err);
+    }
+    std::fs::rename(&temp_path, path)
+        .context(format!("将 {temp_path:?} 重命名为 {path:?} 失败"))?;
+    sync_parent_dir(path)?;
+    Ok(())
+}
+
+/// 与 `path` 同目录的临时路径，例如 `第1话.cbz` 对应 `.写入中-第1话.cbz`
+fn get_temp_path(path: &Path, tag: &str) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{tag}-{file_name}"))
+}
+
+/// 将 `dir` 及其子目录中的所有文件落盘
+fn sync_dir_files(dir: &Path) -> anyhow::Result<()> {
+    for entry in std::fs::read_dir(dir)
+        .context(format!("读取目录 {dir:?} 失败"))?
+        .filter_map(Result::ok)
+    {
+        let path = entry.path();
+        if path.is_file() {
+            sync_file(&path)?;
+        } else if path.is_dir() {
+            sync_dir_files(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Windows 上只有以写入模式打开的文件才能落盘，所以这里以写入模式打开
+fn sync_file(path: &Path) -> anyhow::Result<()> {
+    OpenOptions::new()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.sync_all())
+        .context(format!("将 {path:?} 写入磁盘失败"))
+}
+
+/// 重命名后同步父目录，保证重命名操作本身也被写入磁盘
+///
+/// Windows 不支持打开目录，NTFS 的重命名由日志保证，所以只在 Unix 上同步
+fn sync_parent_dir(path: &Path) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        File::open(parent)
+            .and_then(|dir| dir.sync_all())
+            .context(format!("将目录 {parent:?} 写入磁盘失败"))?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
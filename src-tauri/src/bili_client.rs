@@ -1,11 +1,14 @@
 use crate::config::Config;
 use crate::events::{SetProxyErrorEvent, SetProxyErrorEventPayload};
 use crate::extensions::AnyhowErrorToStringChain;
+use crate::library::Library;
 use crate::responses::{
     BiliResp, ComicRespData, GenerateWebQrcodeRespData, ImageIndexRespData, ImageTokenRespData,
     SearchRespData, UserProfileRespData, WebQrcodeStatusRespData,
 };
+use crate::token_bucket::TokenBucket;
 use crate::types::{AsyncRwLock, Comic, ProxyMode, WebQrcodeData};
+use crate::utils::filename_filter;
 use anyhow::{anyhow, Context};
 use base64::engine::general_purpose;
 use base64::Engine;
@@ -28,13 +31,20 @@ use tauri_specta::Event;
 pub struct BiliClient {
     app: AppHandle,
     http_client: Arc<AsyncRwLock<ClientWithMiddleware>>,
+    /// 所有图片下载共享的限速器
+    download_limiter: Arc<TokenBucket>,
 }
 
 impl BiliClient {
     pub fn new(app: AppHandle) -> Self {
         let http_client = create_http_client(&app);
         let http_client = Arc::new(AsyncRwLock::new(http_client));
-        Self { app, http_client }
+        let download_limiter = Arc::new(TokenBucket::new());
+        Self {
+            app,
+            http_client,
+            download_limiter,
+        }
     }
 
     pub async fn recreate_http_client(&self) {
@@ -284,6 +294,14 @@ impl BiliClient {
         let comic_resp_data = serde_json::from_str::<ComicRespData>(&data_str).context(format!(
             "获取漫画详情失败，将data解析为ComicRespData失败: {data_str}"
         ))?;
+        // 第一次获取该漫画时扫描已下载的章节，之后 `Comic::from` 只需查询索引
+        // 扫描失败时 `Library::is_downloaded` 会直接检查磁盘，所以忽略错误
+        let comic_title = filename_filter(&comic_resp_data.title);
+        let app = self.app.clone();
+        let _ = tauri::async_runtime::spawn_blocking(move || {
+            app.state::<Library>().ensure_scanned(&comic_title)
+        })
+        .await;
         let comic = Comic::from(&self.app, comic_resp_data);
 
         Ok(comic)
@@ -441,18 +459,39 @@ impl BiliClient {
         // 检查http响应状态码
         let status = http_resp.status();
         if status != StatusCode::OK {
+            // 保留reqwest::Error，以便调用方根据状态码判断失败原因(例如403表示ImageToken已过期)
+            let status_err = http_resp.error_for_status_ref().err();
             let body = http_resp.text().await?;
-            return Err(anyhow!("下载图片 {url} 失败，预料之外的状态码: {body}"));
+            let err_msg = format!("下载图片 {url} 失败，预料之外的状态码({status}): {body}");
+            return Err(match status_err {
+                Some(status_err) => anyhow::Error::from(status_err).context(err_msg),
+                None => anyhow!(err_msg),
+            });
+        }
+        // 分块读取图片数据，每读取一块都要从限速器获取对应数量的令牌
+        let mut http_resp = http_resp;
+        let mut image_data = Vec::new();
+        while let Some(chunk) = http_resp.chunk().await? {
+            let speed_limit = self.download_speed_limit();
+            self.download_limiter
+                .acquire(chunk.len() as u64, speed_limit)
+                .await;
+            image_data.extend_from_slice(&chunk);
         }
-        // 读取图片数据
-        let image_data = http_resp.bytes().await?;
 
-        Ok(image_data)
+        Ok(Bytes::from(image_data))
     }
 
     fn cookie(&self) -> String {
         self.app.state::<RwLock<Config>>().read().cookie.clone()
     }
+
+    fn download_speed_limit(&self) -> u64 {
+        self.app
+            .state::<RwLock<Config>>()
+            .read()
+            .download_speed_limit
+    }
 }
 
 fn create_http_client(app: &AppHandle) -> ClientWithMiddleware {
@@ -5,17 +5,30 @@ use anyhow::anyhow;
 use parking_lot::RwLock;
 use path_slash::PathBufExt;
 use reqwest::StatusCode;
-use tauri::{AppHandle, State};
+use tauri::{AppHandle, Manager, State};
 
 use crate::bili_client::BiliClient;
 use crate::config::Config;
+use crate::convert;
+use crate::download_history::DownloadHistory;
 use crate::download_manager::DownloadManager;
 use crate::errors::CommandResult;
+use crate::library::Library;
+use crate::opf;
 use crate::responses::{
     GithubReleasesResp, SearchRespData, UserProfileRespData,
     WebQrcodeStatusRespData,
 };
-use crate::types::{CheckUpdateResult, Comic, EpisodeInfo, WebQrcodeData};
+use crate::series_metadata;
+use crate::subscription_manager::SubscriptionManager;
+use crate::types::{
+    ArchiveCompression, ArchiveFormat, CheckUpdateResult, Comic, CorruptedEpisode,
+    DownloadHistoryEntry, DownloadHistoryQuery, DownloadTask, EpisodeFilter, EpisodeInfo,
+    LibraryComic, LibraryScope, Subscription, VolumeOptions, WebQrcodeData,
+};
+use crate::verify;
+use crate::volume;
+use crate::watermark;
 
 #[tauri::command]
 #[specta::specta]
@@ -36,15 +49,38 @@ pub fn get_config(config: State<RwLock<Config>>) -> Config {
 pub async fn save_config(
     app: AppHandle,
     bili_client: State<'_, BiliClient>,
+    download_manager: State<'_, DownloadManager>,
+    subscription_manager: State<'_, SubscriptionManager>,
+    library: State<'_, Library>,
     config_state: State<'_, RwLock<Config>>,
     config: Config,
 ) -> CommandResult<()> {
-    let need_recreate = {
+    if config.episode_concurrency == 0 || config.image_concurrency == 0 {
+        return Err(anyhow!("同时下载的章节数和图片数都不能为0").into());
+    }
+    if config.image_token_batch_size == 0 {
+        return Err(anyhow!("每批获取的ImageToken数量不能为0").into());
+    }
+    if config.image_interval_min_ms > config.image_interval_max_ms {
+        return Err(anyhow!("图片下载间隔的最小值不能大于最大值").into());
+    }
+    if config.archive_compression == ArchiveCompression::Deflate
+        && !(1..=9).contains(&config.archive_compression_level)
+    {
+        return Err(anyhow!("Deflate的压缩等级必须在1到9之间").into());
+    }
+
+    let (need_recreate, interval_changed, download_dir_changed) = {
         let config_state = config_state.read();
-        config_state.proxy_mode != config.proxy_mode
+        let need_recreate = config_state.proxy_mode != config.proxy_mode
             || config_state.proxy_host != config.proxy_host
-            || config_state.proxy_port != config.proxy_port
+            || config_state.proxy_port != config.proxy_port;
+        let interval_changed = config_state.subscription_check_interval_minutes
+            != config.subscription_check_interval_minutes;
+        let download_dir_changed = config_state.download_dir != config.download_dir;
+        (need_recreate, interval_changed, download_dir_changed)
     };
+    let new_episode_concurrency = config.episode_concurrency;
 
     *config_state.write() = config;
     config_state.write().save(&app)?;
@@ -53,6 +89,15 @@ pub async fn save_config(
         bili_client.recreate_http_client().await;
     }
 
+    download_manager.set_episode_concurrency(new_episode_concurrency);
+    if interval_changed {
+        subscription_manager.notify_interval_changed();
+    }
+    if download_dir_changed {
+        // 索引中的漫画都属于旧的下载目录
+        library.clear();
+    }
+
     Ok(())
 }
 
@@ -97,8 +142,17 @@ pub async fn search(
 
 #[tauri::command(async)]
 #[specta::specta]
-pub async fn get_comic(bili_client: State<'_, BiliClient>, comic_id: i64) -> CommandResult<Comic> {
+pub async fn get_comic(
+    app: AppHandle,
+    config: State<'_, RwLock<Config>>,
+    bili_client: State<'_, BiliClient>,
+    comic_id: i64,
+) -> CommandResult<Comic> {
     let comic = bili_client.get_comic(comic_id).await?;
+    // 元数据只是附加信息，保存失败时不影响浏览漫画
+    if config.read().save_series_metadata {
+        series_metadata::try_save_series_metadata(&app, &comic);
+    }
     Ok(comic)
 }
 
@@ -108,9 +162,68 @@ pub async fn download_episodes(
     download_manager: State<'_, DownloadManager>,
     episodes: Vec<EpisodeInfo>,
 ) -> CommandResult<()> {
-    for ep in episodes {
-        download_manager.submit_episode(ep).await?;
-    }
+    download_manager.submit_episodes(episodes).await?;
+    Ok(())
+}
+
+/// 获取漫画的所有章节，将符合 `filter` 的章节加入下载队列，返回加入下载队列的章节
+///
+/// 已在下载队列中的章节会被跳过
+#[tauri::command(async)]
+#[specta::specta]
+pub async fn download_comic(
+    bili_client: State<'_, BiliClient>,
+    download_manager: State<'_, DownloadManager>,
+    comic_id: i64,
+    filter: EpisodeFilter,
+) -> CommandResult<Vec<EpisodeInfo>> {
+    let comic = bili_client.get_comic(comic_id).await?;
+    let episodes: Vec<EpisodeInfo> = comic
+        .episode_infos
+        .into_iter()
+        .filter(|ep| filter.matches(ep) && !download_manager.contains_task(ep.episode_id))
+        .collect();
+    download_manager.submit_episodes(episodes.clone()).await?;
+    Ok(episodes)
+}
+
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn get_download_tasks(download_manager: State<DownloadManager>) -> Vec<DownloadTask> {
+    download_manager.get_tasks()
+}
+
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn pause_download(
+    download_manager: State<DownloadManager>,
+    episode_id: i64,
+) -> CommandResult<()> {
+    download_manager.pause_download(episode_id)?;
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn resume_download(
+    download_manager: State<DownloadManager>,
+    episode_id: i64,
+) -> CommandResult<()> {
+    download_manager.resume_download(episode_id)?;
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn cancel_download(
+    download_manager: State<DownloadManager>,
+    episode_id: i64,
+) -> CommandResult<()> {
+    download_manager.cancel_download(episode_id)?;
     Ok(())
 }
 
@@ -125,6 +238,167 @@ pub fn show_path_in_file_manager(path: &str) -> CommandResult<()> {
     Ok(())
 }
 
+#[tauri::command(async)]
+#[specta::specta]
+pub async fn subscribe(
+    subscription_manager: State<'_, SubscriptionManager>,
+    comic_id: i64,
+) -> CommandResult<Subscription> {
+    let subscription = subscription_manager.subscribe(comic_id).await?;
+    Ok(subscription)
+}
+
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn unsubscribe(
+    subscription_manager: State<SubscriptionManager>,
+    comic_id: i64,
+) -> CommandResult<()> {
+    subscription_manager.unsubscribe(comic_id)?;
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn list_subscriptions(subscription_manager: State<SubscriptionManager>) -> Vec<Subscription> {
+    subscription_manager.list_subscriptions()
+}
+
+/// 查询符合条件的下载历史，最新的记录排在最前面
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn get_download_history(
+    download_history: State<DownloadHistory>,
+    query: DownloadHistoryQuery,
+) -> CommandResult<Vec<DownloadHistoryEntry>> {
+    let entries = download_history.query(&query)?;
+    Ok(entries)
+}
+
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn clear_download_history(download_history: State<DownloadHistory>) -> CommandResult<()> {
+    download_history.clear()?;
+    Ok(())
+}
+
+/// 去除 `dir_path` 中所有图片的水印，处理进度通过 `RemoveWatermark*Event` 发送
+#[tauri::command(async)]
+#[specta::specta]
+pub async fn remove_watermark(app: AppHandle, dir_path: PathBuf) -> CommandResult<()> {
+    tauri::async_runtime::spawn_blocking(move || watermark::remove_watermark(&app, &dir_path))
+        .await??;
+    Ok(())
+}
+
+/// 将漫画 `comic_id` 中按 `options` 选择的已下载章节合并为单行本，返回单行本的路径
+#[tauri::command(async)]
+#[specta::specta]
+pub async fn merge_episodes(
+    app: AppHandle,
+    config: State<'_, RwLock<Config>>,
+    bili_client: State<'_, BiliClient>,
+    comic_id: i64,
+    options: VolumeOptions,
+) -> CommandResult<PathBuf> {
+    let comic = bili_client.get_comic(comic_id).await?;
+    let episodes: Vec<EpisodeInfo> = comic
+        .episode_infos
+        .into_iter()
+        .filter(|ep| options.selects(ep))
+        .collect();
+    let archive_format = options
+        .archive_format
+        .unwrap_or_else(|| config.read().archive_format.clone());
+    // 只有epub需要封面，封面下载失败时不包含封面
+    let cover = if archive_format == ArchiveFormat::Epub {
+        let cover_data = bili_client.get_image_bytes(&comic.vertical_cover).await;
+        cover_data.ok().map(|data| data.to_vec())
+    } else {
+        None
+    };
+    let volume_path = tauri::async_runtime::spawn_blocking(move || {
+        volume::merge_episodes(&app, &episodes, options.volume, &archive_format, cover)
+    })
+    .await??;
+    Ok(volume_path)
+}
+
+/// 导出漫画 `comic_id` 的Calibre元数据，`volume` 为 `None` 时导出整部漫画的，否则导出第 `volume` 卷单行本的
+///
+/// 返回导出的OPF文件的路径
+#[tauri::command(async)]
+#[specta::specta]
+pub async fn export_opf(
+    app: AppHandle,
+    bili_client: State<'_, BiliClient>,
+    comic_id: i64,
+    volume: Option<i64>,
+) -> CommandResult<PathBuf> {
+    let comic = bili_client.get_comic(comic_id).await?;
+    let opf_path =
+        tauri::async_runtime::spawn_blocking(move || opf::export_opf(&app, &comic, volume))
+            .await??;
+    Ok(opf_path)
+}
+
+/// 在后台将 `scope` 范围内以 `from` 格式保存的章节和单行本转换为 `to` 格式，转换进度通过 `ConvertLibrary*Event` 发送
+///
+/// 同一时间只能进行一次转换，下载队列中有章节的漫画会被跳过
+#[tauri::command(async)]
+#[specta::specta]
+pub async fn convert_library(
+    app: AppHandle,
+    from: ArchiveFormat,
+    to: ArchiveFormat,
+    scope: LibraryScope,
+) -> CommandResult<()> {
+    if from == to {
+        return Err(anyhow!("转换前后的格式相同").into());
+    }
+    let guard = convert::ConvertGuard::acquire()?;
+    let src_paths = convert::get_convert_targets(&app, &from, &scope)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let _guard = guard;
+        convert::convert_library(&app, src_paths, &from, &to);
+    });
+    Ok(())
+}
+
+/// 重新扫描 `scope` 范围内已下载的漫画并更新本地漫画库的索引，返回扫描到的漫画
+#[tauri::command(async)]
+#[specta::specta]
+pub async fn scan_library(app: AppHandle, scope: LibraryScope) -> CommandResult<Vec<LibraryComic>> {
+    let comics =
+        tauri::async_runtime::spawn_blocking(move || app.state::<Library>().scan(&scope)).await??;
+    Ok(comics)
+}
+
+/// 获取本地漫画库索引中的所有漫画，只包含已经扫描过的漫画
+#[tauri::command]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn get_library(library: State<Library>) -> Vec<LibraryComic> {
+    library.get_comics()
+}
+
+/// 离线校验 `scope` 范围内已下载的章节能否正常阅读，返回无法正常阅读的章节，校验进度通过 `VerifyLibraryProgressEvent` 发送
+///
+/// 返回的章节已被重命名，之后会被视为未下载，重新获取漫画信息后即可重新下载，重新下载成功后损坏的章节会被删除
+#[tauri::command(async)]
+#[specta::specta]
+pub async fn verify_library(
+    app: AppHandle,
+    scope: LibraryScope,
+) -> CommandResult<Vec<CorruptedEpisode>> {
+    let corrupted_episodes = verify::verify_library(&app, scope).await?;
+    Ok(corrupted_episodes)
+}
+
 #[tauri::command(async)]
 #[specta::specta]
 pub async fn check_update(app: AppHandle) -> CommandResult<CheckUpdateResult> {
@@ -1,6 +1,6 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use crate::types::{ArchiveFormat, ProxyMode};
+use crate::types::{ArchiveCompression, ArchiveFormat, ProxyMode};
 
 use serde::{Deserialize, Serialize};
 use specta::Type;
@@ -8,36 +8,48 @@ use tauri::{AppHandle, Manager};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Type)]
 #[serde(rename_all = "camelCase")]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Config {
     pub cookie: String,
     pub download_dir: PathBuf,
     pub archive_format: ArchiveFormat,
+    pub archive_compression: ArchiveCompression,
+    /// 只对Deflate有效，范围为1到9
+    pub archive_compression_level: u32,
     pub last_update_check_ts: i64,
     pub proxy_mode: ProxyMode,
     pub proxy_host: String,
     pub proxy_port: u16,
+    pub episode_concurrency: usize,
+    pub image_concurrency: usize,
+    pub image_interval_min_ms: u64,
+    pub image_interval_max_ms: u64,
+    pub image_token_batch_size: usize,
+    pub image_retry_attempts: u32,
+    pub image_retry_backoff_ms: u64,
+    /// 所有下载共享的速度上限，单位为字节/秒，为0表示不限速
+    pub download_speed_limit: u64,
+    pub remove_watermark_before_archive: bool,
+    /// 在漫画目录中保存漫画的封面 `cover.jpg`
+    pub save_comic_cover: bool,
+    /// 将章节封面作为第0页放在cbz的最前面
+    pub prepend_episode_cover: bool,
+    /// 在漫画目录中保存 `series.json` 和 `details.json`，每次获取漫画信息时更新
+    pub save_series_metadata: bool,
+    /// 为0表示不自动检查订阅的漫画
+    pub subscription_check_interval_minutes: u64,
 }
 
 impl Config {
     pub fn new(app: &AppHandle) -> anyhow::Result<Self> {
         let app_data_dir = app.path().app_data_dir()?;
         let config_path = app_data_dir.join("config.json");
-        // TODO: 实现Default trait以替代这种写法
-        let default_config = Config {
-            cookie: String::new(),
-            download_dir: app_data_dir.join("漫画下载"),
-            archive_format: ArchiveFormat::default(),
-            last_update_check_ts: 0,
-            proxy_mode: ProxyMode::default(),
-            proxy_host: String::new(),
-            proxy_port: 7890,
-        };
         // 如果配置文件存在且能够解析，则使用配置文件中的配置，否则使用默认配置
         let config = if config_path.exists() {
             let config_string = std::fs::read_to_string(config_path)?;
-            serde_json::from_str(&config_string).unwrap_or(default_config)
+            Self::merge_with_default(&config_string, &app_data_dir)
         } else {
-            default_config
+            Self::default(&app_data_dir)
         };
         config.save(app)?;
         Ok(config)
@@ -50,4 +62,50 @@ impl Config {
         std::fs::write(config_path, config_string)?;
         Ok(())
     }
+
+    fn default(app_data_dir: &Path) -> Self {
+        Config {
+            cookie: String::new(),
+            download_dir: app_data_dir.join("漫画下载"),
+            archive_format: ArchiveFormat::default(),
+            archive_compression: ArchiveCompression::default(),
+            archive_compression_level: 6,
+            last_update_check_ts: 0,
+            proxy_mode: ProxyMode::default(),
+            proxy_host: String::new(),
+            proxy_port: 7890,
+            episode_concurrency: 1,
+            image_concurrency: 1,
+            image_interval_min_ms: 300,
+            image_interval_max_ms: 800,
+            image_token_batch_size: 100,
+            image_retry_attempts: 3,
+            image_retry_backoff_ms: 1000,
+            download_speed_limit: 0,
+            remove_watermark_before_archive: false,
+            save_comic_cover: false,
+            prepend_episode_cover: false,
+            save_series_metadata: false,
+            subscription_check_interval_minutes: 60,
+        }
+    }
+
+    /// 用默认配置补全配置文件中缺失的字段
+    ///
+    /// 新版本增加配置项后，旧的配置文件会缺少这些字段，直接解析会失败并导致所有配置被重置
+    fn merge_with_default(config_string: &str, app_data_dir: &Path) -> Self {
+        let default_config = Self::default(app_data_dir);
+        let Ok(serde_json::Value::Object(mut config_map)) = serde_json::from_str(config_string)
+        else {
+            return default_config;
+        };
+        let Ok(serde_json::Value::Object(default_map)) = serde_json::to_value(&default_config)
+        else {
+            return default_config;
+        };
+        for (key, value) in default_map {
+            config_map.entry(key).or_insert(value);
+        }
+        serde_json::from_value(serde_json::Value::Object(config_map)).unwrap_or(default_config)
+    }
 }
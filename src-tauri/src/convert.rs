@@ -0,0 +1,258 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::{anyhow, Context};
+use parking_lot::RwLock;
+use tauri::{AppHandle, Manager};
+use tauri_specta::Event;
+
+use crate::archive;
+use crate::config::Config;
+use crate::download_manager::DownloadManager;
+use crate::epub::{self, EpubMetadata};
+use crate::events;
+use crate::extensions::AnyhowErrorToStringChain;
+use crate::library;
+use crate::library::Library;
+use crate::pdf;
+use crate::types::{ArchiveFormat, ComicInfo, LibraryScope};
+
+/// 是否有正在进行的转换，同一时间只允许进行一次转换
+static IS_CONVERTING: AtomicBool = AtomicBool::new(false);
+
+/// 转换期间持有，被drop时表示转换结束
+pub struct ConvertGuard(());
+
+impl ConvertGuard {
+    pub fn acquire() -> anyhow::Result<Self> {
+        if IS_CONVERTING.swap(true, Ordering::AcqRel) {
+            return Err(anyhow!("已有正在进行的转换，请等待其结束"));
+        }
+        Ok(Self(()))
+    }
+}
+
+impl Drop for ConvertGuard {
+    fn drop(&mut self) {
+        IS_CONVERTING.store(false, Ordering::Release);
+    }
+}
+
+/// 获取 `scope` 范围内所有以 `from` 格式保存的章节和单行本
+pub fn get_convert_targets(
+    app: &AppHandle,
+    from: &ArchiveFormat,
+    scope: &LibraryScope,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let download_dir = app.state::<RwLock<Config>>().read().download_dir.clone();
+    let mut src_paths = Vec::new();
+    for comic_dir in library::get_comic_dirs(&download_dir, scope)? {
+        src_paths.extend(library::get_archive_paths(&comic_dir, from)?);
+    }
+    Ok(src_paths)
+}
+
+/// 将 `src_paths` 中以 `from` 格式保存的章节和单行本依次转换为 `to` 格式，并发送 `ConvertLibrary*Event`
+///
+/// 单个章节转换失败只会发送 `ConvertLibraryErrorEvent`，不会中断其他章节的转换，
+/// 下载队列中有章节的漫画正在被写入，会被跳过
+pub fn convert_library(
+    app: &AppHandle,
+    src_paths: Vec<PathBuf>,
+    from: &ArchiveFormat,
+    to: &ArchiveFormat,
+) {
+    #[allow(clippy::cast_possible_truncation)]
+    let total = src_paths.len() as u32;
+    emit_start_event(app, total);
+    let mut current = 0;
+    let mut failed = 0;
+    for src_path in src_paths {
+        let comic_title = src_path
+            .parent()
+            .and_then(Path::file_name)
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if app.state::<DownloadManager>().contains_comic(&comic_title) {
+            failed += 1;
+            let err_msg = format!("漫画 {comic_title} 有章节在下载队列中，已跳过 {src_path:?}");
+            emit_error_event(app, src_path, err_msg);
+            continue;
+        }
+        match convert_archive(app, &src_path, from, to) {
+            Ok(dest_path) => {
+                let library = app.state::<Library>();
+                library.remove_archive(&src_path);
+                library.index_archive(&dest_path, to);
+                current += 1;
+                emit_success_event(app, src_path, dest_path, current);
+            }
+            Err(err) => {
+                failed += 1;
+                let err_msg = err
+                    .context(format!("转换 {src_path:?} 失败"))
+                    .to_string_chain();
+                emit_error_event(app, src_path, err_msg);
+            }
+        }
+    }
+    emit_end_event(app, current, failed);
+}
+
+/// 将 `src_path` 转换为 `to` 格式并保存到同一目录中，转换成功后删除 `src_path`，返回转换后的路径
+///
+/// 优先沿用原有的 `ComicInfo.xml`，pdf和epub中没有 `ComicInfo.xml`，此时根据目录名重新生成，
+/// epub的阅读方向会被记录到重新生成的 `ComicInfo` 的 `Manga` 中。
+/// 单行本中的章节会按顺序合并为一个章节
+fn convert_archive(
+    app: &AppHandle,
+    src_path: &Path,
+    from: &ArchiveFormat,
+    to: &ArchiveFormat,
+) -> anyhow::Result<PathBuf> {
+    let Some(comic_dir) = src_path.parent() else {
+        return Err(anyhow!("无法获取 {src_path:?} 的父目录"));
+    };
+    let name = library::get_archive_name(src_path, from);
+    let dest_path = archive::get_archive_path(comic_dir, &name, to);
+    if dest_path.exists() {
+        return Err(anyhow!("{dest_path:?} 已存在"));
+    }
+    // 以 `.转换中-` 开头，表示是临时目录
+    let temp_dir = comic_dir.join(format!(".转换中-{name}"));
+    if temp_dir.exists() {
+        std::fs::remove_dir_all(&temp_dir).context(format!("删除 {temp_dir:?} 失败"))?;
+    }
+    std::fs::create_dir_all(&temp_dir).context(format!("创建目录 {temp_dir:?} 失败"))?;
+
+    let result = archive::extract_pages(src_path, from, &temp_dir)
+        .context(format!("提取 {src_path:?} 中的图片失败"))
+        .and_then(|()| get_comic_info(src_path, from, comic_dir, &name, &temp_dir))
+        .and_then(|comic_info| save_as(app, &temp_dir, &comic_info, &dest_path, to));
+    if let Err(err) = result {
+        // 转换失败，临时目录已经没有用了
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        return Err(err);
+    }
+
+    if *from == ArchiveFormat::Image {
+        std::fs::remove_dir_all(src_path).context(format!("删除 {src_path:?} 失败"))?;
+    } else {
+        std::fs::remove_file(src_path).context(format!("删除 {src_path:?} 失败"))?;
+    }
+    Ok(dest_path)
+}
+
+/// 读取 `src_path` 中的 `ComicInfo.xml`，没有时用漫画目录名和章节名生成，阅读方向来自epub的翻页方向
+fn get_comic_info(
+    src_path: &Path,
+    from: &ArchiveFormat,
+    comic_dir: &Path,
+    name: &str,
+    temp_dir: &Path,
+) -> anyhow::Result<ComicInfo> {
+    if let Some(comic_info) = archive::read_comic_info(src_path, from)? {
+        return Ok(comic_info);
+    }
+    let series = comic_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let page_count = archive::get_sorted_page_paths(temp_dir)?.len();
+    let is_right_to_left = *from == ArchiveFormat::Epub && epub::read_is_right_to_left(src_path)?;
+    Ok(ComicInfo {
+        manga: ComicInfo::get_manga(is_right_to_left),
+        series,
+        publisher: "哔哩哔哩漫画".to_string(),
+        title: name.to_string(),
+        page_count: i64::try_from(page_count).unwrap_or(i64::MAX),
+        language_iso: "zh".to_string(),
+        ..Default::default()
+    })
+}
+
+/// 将 `temp_dir` 中的图片以 `archive_format` 格式保存为 `dest_path`，保存成功后 `temp_dir` 会被移走或删除
+fn save_as(
+    app: &AppHandle,
+    temp_dir: &Path,
+    comic_info: &ComicInfo,
+    dest_path: &Path,
+    archive_format: &ArchiveFormat,
+) -> anyhow::Result<()> {
+    let (compression, compression_level) = {
+        let config = app.state::<RwLock<Config>>();
+        let config = config.read();
+        (
+            config.archive_compression.clone(),
+            config.archive_compression_level,
+        )
+    };
+    match archive_format {
+        ArchiveFormat::Image => {
+            let comic_info = archive::comic_info_with_pages(comic_info, temp_dir, &[])?;
+            let comic_info_path = temp_dir.join(archive::COMIC_INFO_FILENAME);
+            std::fs::write(&comic_info_path, archive::to_comic_info_xml(&comic_info)?)
+                .context(format!("写入 {comic_info_path:?} 失败"))?;
+            archive::save_image_dir(temp_dir, dest_path)?;
+        }
+        ArchiveFormat::Zip | ArchiveFormat::Cbz => {
+            archive::save_zip(
+                temp_dir,
+                comic_info,
+                dest_path,
+                &compression,
+                compression_level,
+                &[],
+            )?;
+        }
+        ArchiveFormat::Pdf => pdf::save_pdf(temp_dir, comic_info, dest_path, &[])?,
+        ArchiveFormat::Epub => {
+            let identifier = if comic_info.web.is_empty() {
+                format!("{} - {}", comic_info.series, comic_info.title)
+            } else {
+                comic_info.web.clone()
+            };
+            // 漫画目录中保存了漫画封面时使用它作为封面
+            let cover = dest_path
+                .parent()
+                .and_then(|comic_dir| std::fs::read(comic_dir.join("cover.jpg")).ok());
+            let metadata = EpubMetadata {
+                identifier,
+                comic_info: comic_info.clone(),
+                cover,
+                is_right_to_left: comic_info.is_right_to_left(),
+            };
+            let options = archive::zip_file_options(&compression, compression_level);
+            epub::save_epub(temp_dir, &metadata, dest_path, &[], options)?;
+        }
+    }
+    Ok(())
+}
+
+fn emit_start_event(app: &AppHandle, total: u32) {
+    let payload = events::ConvertLibraryStartEventPayload { total };
+    let event = events::ConvertLibraryStartEvent(payload);
+    let _ = event.emit(app);
+}
+
+fn emit_success_event(app: &AppHandle, src_path: PathBuf, dest_path: PathBuf, current: u32) {
+    let payload = events::ConvertLibrarySuccessEventPayload {
+        src_path,
+        dest_path,
+        current,
+    };
+    let event = events::ConvertLibrarySuccessEvent(payload);
+    let _ = event.emit(app);
+}
+
+fn emit_error_event(app: &AppHandle, src_path: PathBuf, err_msg: String) {
+    let payload = events::ConvertLibraryErrorEventPayload { src_path, err_msg };
+    let event = events::ConvertLibraryErrorEvent(payload);
+    let _ = event.emit(app);
+}
+
+fn emit_end_event(app: &AppHandle, converted: u32, failed: u32) {
+    let payload = events::ConvertLibraryEndEventPayload { converted, failed };
+    let event = events::ConvertLibraryEndEvent(payload);
+    let _ = event.emit(app);
+}
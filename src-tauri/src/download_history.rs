@@ -0,0 +1,75 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use parking_lot::Mutex;
+use tauri::{AppHandle, Manager};
+
+use crate::types::{DownloadHistoryEntry, DownloadHistoryQuery};
+
+/// 下载历史，每条记录占一行追加到 `download_history.jsonl` 中
+pub struct DownloadHistory {
+    app: AppHandle,
+    // 保证多个章节同时结束时，每条记录都完整地写入一行
+    file_lock: Mutex<()>,
+}
+
+impl DownloadHistory {
+    pub fn new(app: &AppHandle) -> Self {
+        Self {
+            app: app.clone(),
+            file_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn record(&self, entry: &DownloadHistoryEntry) -> anyhow::Result<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        let history_path = self.history_path()?;
+        let _guard = self.file_lock.lock();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&history_path)
+            .context(format!("打开 {history_path:?} 失败"))?;
+        file.write_all(line.as_bytes())
+            .context(format!("写入 {history_path:?} 失败"))?;
+        Ok(())
+    }
+
+    /// 查询符合条件的下载记录，最新的记录排在最前面
+    pub fn query(&self, query: &DownloadHistoryQuery) -> anyhow::Result<Vec<DownloadHistoryEntry>> {
+        let history_path = self.history_path()?;
+        if !history_path.exists() {
+            return Ok(vec![]);
+        }
+        let history_string = {
+            let _guard = self.file_lock.lock();
+            std::fs::read_to_string(&history_path).context(format!("读取 {history_path:?} 失败"))?
+        };
+        // 写入过程中崩溃可能导致最后一行不完整，跳过无法解析的行
+        let entries = history_string
+            .lines()
+            .rev()
+            .filter_map(|line| serde_json::from_str::<DownloadHistoryEntry>(line).ok())
+            .filter(|entry| query.matches(entry))
+            .take(query.limit.unwrap_or(usize::MAX))
+            .collect();
+        Ok(entries)
+    }
+
+    pub fn clear(&self) -> anyhow::Result<()> {
+        let history_path = self.history_path()?;
+        let _guard = self.file_lock.lock();
+        if history_path.exists() {
+            std::fs::remove_file(&history_path).context(format!("删除 {history_path:?} 失败"))?;
+        }
+        Ok(())
+    }
+
+    fn history_path(&self) -> anyhow::Result<PathBuf> {
+        let app_data_dir = self.app.path().app_data_dir()?;
+        Ok(app_data_dir.join("download_history.jsonl"))
+    }
+}
@@ -1,40 +1,119 @@
+use crate::archive;
+use crate::archive::PageSize;
 use crate::bili_client::BiliClient;
 use crate::config::Config;
+use crate::download_history::DownloadHistory;
+use crate::epub;
+use crate::epub::EpubMetadata;
 use crate::events;
-use crate::events::{DownloadSpeedEvent, DownloadSpeedEventPayload};
+use crate::events::{DownloadSpeedEvent, DownloadSpeedEventPayload, EpisodeDownloadSpeed};
 use crate::extensions::AnyhowErrorToStringChain;
-use crate::types::{ArchiveFormat, EpisodeInfo};
+use crate::library::Library;
+use crate::pdf;
+use crate::series_metadata;
+use crate::types::{
+    ArchiveFormat, DownloadHistoryEntry, DownloadTask, DownloadTaskState, EpisodeInfo,
+};
+use crate::utils;
+use crate::watermark;
 use aes::cipher::consts::U16;
 use aes::cipher::generic_array::GenericArray;
 use aes::cipher::{BlockDecrypt, KeyInit};
 use aes::Aes256;
-use std::fs::File;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicI64, AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, Context};
 use base64::engine::general_purpose;
 use base64::Engine;
 use byteorder::{BigEndian, ByteOrder};
 use bytes::Bytes;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use percent_encoding::percent_decode_str;
 use rand::Rng;
+use reqwest::StatusCode;
 use tauri::{AppHandle, Manager};
 use tauri_specta::Event;
 use tokio::sync::mpsc::Receiver;
-use tokio::sync::{mpsc, Semaphore};
+use tokio::sync::{mpsc, watch, Semaphore, SemaphorePermit};
+use tokio::task::{JoinError, JoinSet};
 use url::Url;
-use zip::write::SimpleFileOptions;
-use zip::ZipWriter;
 
 // TODO: EpisodeInfo与AlbumPlusItem的内存差距过大，应该用Box包裹EpisodeInfo
 enum DownloadPayload {
     Episode(EpisodeInfo),
 }
 
+/// `ImageToken` 的有效期，超过有效期的 `ImageToken` 会被重新获取
+///
+/// 这只是保守的估计，即使 `ImageToken` 在有效期内失效，下载时返回403也会触发重新获取
+const IMAGE_TOKEN_TTL: Duration = Duration::from_secs(600);
+/// 章节临时下载目录名的前缀，以 `.` 开头表示是临时目录
+pub const DOWNLOADING_DIR_PREFIX: &str = ".下载中-";
+
+struct DownloadTaskEntry {
+    ep_info: EpisodeInfo,
+    state_sender: watch::Sender<DownloadTaskState>,
+    /// 提交顺序，用于持久化时保持任务的先后顺序
+    seq: u64,
+    progress: EpisodeProgress,
+}
+
+/// 章节的下载进度，用于计算章节的下载速度和预计剩余时间
+#[derive(Default)]
+struct EpisodeProgress {
+    /// 章节的图片总数，为0表示还没开始下载
+    total_pages: AtomicU32,
+    /// 已下载的图片数量，包括临时目录中已存在的图片
+    downloaded_pages: AtomicU32,
+    /// 本次下载的图片数量，用于估计图片的平均大小
+    fetched_pages: AtomicU32,
+    /// 本次下载的字节数
+    downloaded_bytes: AtomicU64,
+    /// 上次发送下载速度事件以来下载的字节数
+    byte_per_sec: AtomicU64,
+    /// 开始下载的时间戳，单位为秒，为0表示还没开始下载
+    started_at: AtomicI64,
+}
+
+impl EpisodeProgress {
+    /// 计算章节的下载速度和预计剩余时间，同时重置下载速度的计数，还没开始下载则返回 `None`
+    fn take_speed(&self, id: i64) -> Option<EpisodeDownloadSpeed> {
+        let total_pages = self.total_pages.load(Ordering::Relaxed);
+        if total_pages == 0 {
+            return None;
+        }
+        let bytes_per_sec = self.byte_per_sec.swap(0, Ordering::Relaxed);
+        let downloaded_bytes = self.downloaded_bytes.load(Ordering::Relaxed);
+        let fetched_pages = u64::from(self.fetched_pages.load(Ordering::Relaxed));
+        let downloaded_pages = self.downloaded_pages.load(Ordering::Relaxed);
+        let remaining_pages = total_pages.saturating_sub(downloaded_pages);
+        // 本次还没下载过图片时无法估计图片的平均大小
+        let remaining_bytes = downloaded_bytes
+            .checked_div(fetched_pages)
+            .map(|avg_page_bytes| avg_page_bytes * u64::from(remaining_pages));
+        let (remaining_bytes, eta_secs) = match remaining_bytes {
+            _ if remaining_pages == 0 => (0, Some(0)),
+            Some(remaining_bytes) => (
+                remaining_bytes,
+                estimate_eta_secs(remaining_bytes, bytes_per_sec),
+            ),
+            None => (0, None),
+        };
+        Some(EpisodeDownloadSpeed {
+            id,
+            bytes_per_sec,
+            downloaded_bytes,
+            remaining_pages,
+            remaining_bytes,
+            eta_secs,
+        })
+    }
+}
+
 /// 用于管理下载任务
 ///
 /// 克隆 `DownloadManager` 的开销极小，性能开销几乎可以忽略不计。
@@ -48,18 +127,32 @@ pub struct DownloadManager {
     app: AppHandle,
     sender: Arc<mpsc::Sender<DownloadPayload>>,
     ep_sem: Arc<Semaphore>,
+    ep_permit_count: Arc<Mutex<EpPermitCount>>,
     byte_per_sec: Arc<AtomicU64>,
+    total_bytes: Arc<AtomicU64>,
+    tasks: Arc<RwLock<HashMap<i64, DownloadTaskEntry>>>,
+    next_task_seq: Arc<AtomicU64>,
+    save_tasks_lock: Arc<Mutex<()>>,
 }
 
 impl DownloadManager {
     pub fn new(app: &AppHandle) -> Self {
         let (sender, receiver) = mpsc::channel::<DownloadPayload>(32);
+        let episode_concurrency = app.state::<RwLock<Config>>().read().episode_concurrency;
 
         let manager = DownloadManager {
             app: app.clone(),
             sender: Arc::new(sender),
-            ep_sem: Arc::new(Semaphore::new(1)),
+            ep_sem: Arc::new(Semaphore::new(episode_concurrency.max(1))),
+            ep_permit_count: Arc::new(Mutex::new(EpPermitCount {
+                target: episode_concurrency.max(1),
+                total: episode_concurrency.max(1),
+            })),
             byte_per_sec: Arc::new(AtomicU64::new(0)),
+            total_bytes: Arc::new(AtomicU64::new(0)),
+            tasks: Arc::new(RwLock::new(HashMap::new())),
+            next_task_seq: Arc::new(AtomicU64::new(0)),
+            save_tasks_lock: Arc::new(Mutex::new(())),
         };
 
         tauri::async_runtime::spawn(Self::log_download_speed(app.clone()));
@@ -68,13 +161,184 @@ impl DownloadManager {
         manager
     }
 
-    pub async fn submit_episode(&self, ep_info: EpisodeInfo) -> anyhow::Result<()> {
+    /// 将 `episodes` 加入下载队列，全部加入后只保存一次任务列表
+    pub async fn submit_episodes(&self, episodes: Vec<EpisodeInfo>) -> anyhow::Result<()> {
+        if episodes.is_empty() {
+            return Ok(());
+        }
+        let mut result = Ok(());
+        for ep_info in episodes {
+            result = self
+                .submit_task(ep_info, DownloadTaskState::default())
+                .await;
+            if result.is_err() {
+                break;
+            }
+        }
+        // 即使中途失败，已经加入队列的章节也需要保存
+        self.save_tasks()?;
+        result
+    }
+
+    /// 恢复上次退出时还在队列中的下载任务，失败时发送 `RestoreDownloadTasksErrorEvent`
+    pub async fn restore_tasks(&self) {
+        if let Err(err) = self.try_restore_tasks().await {
+            let err = err.context("恢复下载任务失败");
+            emit_restore_tasks_error_event(&self.app, err.to_string_chain());
+        }
+    }
+
+    async fn try_restore_tasks(&self) -> anyhow::Result<()> {
+        let tasks_path = self.tasks_path()?;
+        if !tasks_path.exists() {
+            return Ok(());
+        }
+        let tasks_string =
+            std::fs::read_to_string(&tasks_path).context(format!("读取 {tasks_path:?} 失败"))?;
+        let tasks: Vec<DownloadTask> = match serde_json::from_str(&tasks_string) {
+            Ok(tasks) => tasks,
+            Err(err) => {
+                // 文件损坏无法解析时保留为备份，避免之后保存任务时覆盖掉，导致队列彻底丢失
+                let backup_path = tasks_path.with_extension("json.bak");
+                std::fs::rename(&tasks_path, &backup_path)
+                    .context(format!("将 {tasks_path:?} 重命名为 {backup_path:?} 失败"))?;
+                return Err(anyhow::Error::from(err).context(format!(
+                    "解析 {tasks_path:?} 失败，已备份为 {backup_path:?}"
+                )));
+            }
+        };
+        for task in tasks {
+            if task.state == DownloadTaskState::Cancelled {
+                continue;
+            }
+            self.submit_task(task.ep_info, task.state).await?;
+        }
+        self.save_tasks()?;
+        Ok(())
+    }
+
+    /// 下载队列中是否有漫画 `comic_title` 的章节，包括暂停的章节
+    pub fn contains_comic(&self, comic_title: &str) -> bool {
+        self.tasks
+            .read()
+            .values()
+            .any(|entry| entry.ep_info.comic_title == comic_title)
+    }
+
+    pub fn contains_task(&self, episode_id: i64) -> bool {
+        self.tasks.read().contains_key(&episode_id)
+    }
+
+    pub fn get_tasks(&self) -> Vec<DownloadTask> {
+        let tasks = self.tasks.read();
+        let mut entries: Vec<&DownloadTaskEntry> = tasks.values().collect();
+        entries.sort_by_key(|entry| entry.seq);
+        entries
+            .into_iter()
+            .map(|entry| DownloadTask {
+                ep_info: entry.ep_info.clone(),
+                state: *entry.state_sender.borrow(),
+            })
+            .collect()
+    }
+
+    async fn submit_task(
+        &self,
+        ep_info: EpisodeInfo,
+        state: DownloadTaskState,
+    ) -> anyhow::Result<()> {
+        let episode_id = ep_info.episode_id;
+        {
+            let mut tasks = self.tasks.write();
+            if tasks.contains_key(&episode_id) {
+                let comic_title = &ep_info.comic_title;
+                let episode_title = &ep_info.episode_title;
+                return Err(anyhow!("{comic_title} - {episode_title} 已在下载队列中"));
+            }
+            let (state_sender, _) = watch::channel(state);
+            let entry = DownloadTaskEntry {
+                ep_info: ep_info.clone(),
+                state_sender,
+                seq: self.next_task_seq.fetch_add(1, Ordering::Relaxed),
+                progress: EpisodeProgress::default(),
+            };
+            tasks.insert(episode_id, entry);
+        }
         let value = DownloadPayload::Episode(ep_info);
-        self.sender.send(value).await?;
+        if let Err(err) = self.sender.send(value).await {
+            self.tasks.write().remove(&episode_id);
+            return Err(err.into());
+        }
+        Ok(())
+    }
+
+    /// 将队列中的任务保存到 `download_tasks.json`，以便下次启动时恢复
+    fn save_tasks(&self) -> anyhow::Result<()> {
+        // 保证快照与写入的顺序一致，避免旧的快照覆盖新的快照
+        let _guard = self.save_tasks_lock.lock();
+        let tasks: Vec<DownloadTask> = self
+            .get_tasks()
+            .into_iter()
+            .filter(|task| task.state != DownloadTaskState::Cancelled)
+            .collect();
+        let tasks_path = self.tasks_path()?;
+        let tasks_string = serde_json::to_string_pretty(&tasks)?;
+        // 先写入临时文件再重命名，避免写入过程中崩溃导致文件损坏
+        let temp_tasks_path = tasks_path.with_extension("json.tmp");
+        std::fs::write(&temp_tasks_path, tasks_string)
+            .context(format!("写入 {temp_tasks_path:?} 失败"))?;
+        std::fs::rename(&temp_tasks_path, &tasks_path).context(format!(
+            "将 {temp_tasks_path:?} 重命名为 {tasks_path:?} 失败"
+        ))?;
+        Ok(())
+    }
+
+    fn tasks_path(&self) -> anyhow::Result<PathBuf> {
+        let app_data_dir = self.app.path().app_data_dir()?;
+        Ok(app_data_dir.join("download_tasks.json"))
+    }
+
+    pub fn pause_download(&self, episode_id: i64) -> anyhow::Result<()> {
+        self.set_task_state(episode_id, DownloadTaskState::Paused)?;
+        emit_pause_event(&self.app, episode_id);
+        self.save_tasks()?;
+        Ok(())
+    }
+
+    pub fn resume_download(&self, episode_id: i64) -> anyhow::Result<()> {
+        self.set_task_state(episode_id, DownloadTaskState::Running)?;
+        emit_resume_event(&self.app, episode_id);
+        self.save_tasks()?;
+        Ok(())
+    }
+
+    pub fn cancel_download(&self, episode_id: i64) -> anyhow::Result<()> {
+        self.set_task_state(episode_id, DownloadTaskState::Cancelled)?;
+        emit_cancel_event(&self.app, episode_id);
+        self.save_tasks()?;
+        Ok(())
+    }
+
+    fn set_task_state(&self, episode_id: i64, new_state: DownloadTaskState) -> anyhow::Result<()> {
+        let tasks = self.tasks.read();
+        let Some(state_sender) = tasks.get(&episode_id).map(|entry| &entry.state_sender) else {
+            return Err(anyhow!("章节 `{episode_id}` 不在下载队列中"));
+        };
+        let current_state = *state_sender.borrow();
+        let is_valid_transition = match new_state {
+            DownloadTaskState::Running => current_state == DownloadTaskState::Paused,
+            DownloadTaskState::Paused => current_state == DownloadTaskState::Running,
+            DownloadTaskState::Cancelled => current_state != DownloadTaskState::Cancelled,
+        };
+        if !is_valid_transition {
+            return Err(anyhow!(
+                "无法将章节 `{episode_id}` 的下载状态从 {current_state:?} 变为 {new_state:?}"
+            ));
+        }
+        state_sender.send_replace(new_state);
         Ok(())
     }
 
-    #[allow(clippy::cast_precision_loss)]
     // TODO: 换个函数名，如emit_download_speed_loop
     async fn log_download_speed(app: AppHandle) {
         let mut interval = tokio::time::interval(Duration::from_secs(1));
@@ -82,10 +346,28 @@ impl DownloadManager {
         loop {
             interval.tick().await;
             let manager = app.state::<DownloadManager>();
-            let byte_per_sec = manager.byte_per_sec.swap(0, Ordering::Relaxed);
-            let mega_byte_per_sec = byte_per_sec as f64 / 1024.0 / 1024.0;
-            let speed = format!("{mega_byte_per_sec:.2} MB/s");
-            emit_download_speed_event(&app, speed);
+            let bytes_per_sec = manager.byte_per_sec.swap(0, Ordering::Relaxed);
+            let total_bytes = manager
+                .total_bytes
+                .fetch_add(bytes_per_sec, Ordering::Relaxed)
+                + bytes_per_sec;
+            let mut episodes: Vec<EpisodeDownloadSpeed> = manager
+                .tasks
+                .read()
+                .iter()
+                .filter_map(|(id, entry)| entry.progress.take_speed(*id))
+                .collect();
+            episodes.sort_by_key(|episode| episode.id);
+            let remaining_bytes = episodes.iter().map(|episode| episode.remaining_bytes).sum();
+            let eta_secs = estimate_eta_secs(remaining_bytes, bytes_per_sec);
+            let payload = DownloadSpeedEventPayload {
+                bytes_per_sec,
+                total_bytes,
+                remaining_bytes,
+                eta_secs,
+                episodes,
+            };
+            emit_download_speed_event(&app, payload);
         }
     }
 
@@ -100,8 +382,29 @@ impl DownloadManager {
         }
     }
 
-    #[allow(clippy::cast_possible_truncation)]
     async fn process_episode(self, ep_info: EpisodeInfo) {
+        let episode_id = ep_info.episode_id;
+        let state_receiver = self
+            .tasks
+            .read()
+            .get(&episode_id)
+            .map(|entry| entry.state_sender.subscribe());
+        if let Some(state_receiver) = state_receiver {
+            self.download_episode(ep_info, state_receiver).await;
+        }
+        // 无论下载成功、失败还是被取消，都将该章节从任务列表中移除
+        self.tasks.write().remove(&episode_id);
+        // 此时章节的下载已经结束，保存失败也只会导致下次启动时重新下载该章节
+        let _ = self.save_tasks();
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::too_many_lines)]
+    async fn download_episode(
+        &self,
+        ep_info: EpisodeInfo,
+        mut state_receiver: watch::Receiver<DownloadTaskState>,
+    ) {
         emit_pending_event(
             &self.app,
             ep_info.episode_id,
@@ -109,15 +412,24 @@ impl DownloadManager {
             ep_info.episode_title.clone(),
         );
         // 限制同时下载的章节数量
-        let permit = match self.ep_sem.acquire().await.map_err(anyhow::Error::from) {
-            Ok(permit) => permit,
+        let mut permit = match self.acquire_ep_permit(&mut state_receiver).await {
+            Ok(Some(permit)) => permit,
+            // 任务在等待期间被取消，删除之前下载失败时残留的临时下载目录
+            Ok(None) => {
+                remove_temp_download_dir(&get_ep_temp_download_dir(&self.app, &ep_info));
+                return;
+            }
             Err(err) => {
                 let err = err.context("获取下载章节的semaphore失败");
                 let err_msg = err.to_string_chain();
-                emit_end_event(&self.app, ep_info.episode_id, Some(err_msg));
+                self.end_episode(&ep_info, None, Some(err_msg), vec![]);
                 return;
             }
         };
+        let started_at = chrono::Local::now().timestamp();
+        self.update_progress(ep_info.episode_id, |progress| {
+            progress.started_at.store(started_at, Ordering::Relaxed);
+        });
         // 获取path_urls
         let bili_client = self.bili_client();
         let image_index_resp_data = match bili_client
@@ -131,9 +443,8 @@ impl DownloadManager {
                 let err = err.context(format!(
                     "获取 {comic_title} - {chapter_title} 的ImageIndex失败"
                 ));
-                let id = ep_info.episode_id;
                 let err_msg = err.to_string_chain();
-                emit_end_event(&self.app, id, Some(err_msg));
+                self.end_episode(&ep_info, None, Some(err_msg), vec![]);
                 return;
             }
         };
@@ -142,153 +453,583 @@ impl DownloadManager {
             .iter()
             .map(|img| img.path.clone())
             .collect();
-        // 发送下载开始事件
+        // 保存为pdf时，每页的尺寸与图片的原始尺寸一致
+        let page_sizes: Vec<PageSize> = image_index_resp_data
+            .images
+            .iter()
+            .map(|img| PageSize {
+                width: u32::try_from(img.x).unwrap_or(0),
+                height: u32::try_from(img.y).unwrap_or(0),
+            })
+            .collect();
         let total = path_urls.len() as u32;
-        emit_start_event(&self.app, ep_info.episode_id, total);
-        // 准备下载需要的变量
-        let mut current = 0;
         // 下载前先创建临时下载目录
         let temp_download_dir = get_ep_temp_download_dir(&self.app, &ep_info);
         if let Err(err) = std::fs::create_dir_all(&temp_download_dir).map_err(anyhow::Error::from) {
-            let id = ep_info.episode_id;
             let err = err.context(format!("创建目录 {temp_download_dir:?} 失败"));
             let err_msg = err.to_string_chain();
-            emit_end_event(&self.app, id, Some(err_msg));
+            self.end_episode(&ep_info, None, Some(err_msg), vec![]);
             return;
         }
-        // 逐一下载图片
+        // 上次下载失败时残留在临时目录中的图片，只要能正常解码就无需重新下载
+        // 解码很耗时，所以放到阻塞线程中进行，检查失败时全部重新下载
+        let mut is_downloaded_flags: Vec<bool> = {
+            let temp_download_dir = temp_download_dir.clone();
+            let page_count = path_urls.len();
+            tauri::async_runtime::spawn_blocking(move || {
+                (1..=page_count)
+                    .map(|i| is_valid_image(&temp_download_dir.join(format!("{i:03}.jpg"))))
+                    .collect()
+            })
+            .await
+            .unwrap_or_else(|_| vec![false; path_urls.len()])
+        };
+        let downloaded = is_downloaded_flags.iter().filter(|flag| **flag).count() as u32;
+        // 只有需要下载的图片才需要获取ImageToken
+        let pending_path_urls = path_urls
+            .iter()
+            .zip(&is_downloaded_flags)
+            .filter(|(_, is_downloaded)| !**is_downloaded)
+            .map(|(path_url, _)| path_url.clone())
+            .collect();
+        let token_cache = Arc::new(ImageTokenCache::new(
+            ep_info.comic_id,
+            ep_info.episode_id,
+            pending_path_urls,
+        ));
+        // 发送下载开始事件
+        self.update_progress(ep_info.episode_id, |progress| {
+            progress.total_pages.store(total, Ordering::Relaxed);
+            progress
+                .downloaded_pages
+                .store(downloaded, Ordering::Relaxed);
+        });
+        emit_start_event(&self.app, ep_info.episode_id, total, downloaded);
+        // 准备下载需要的变量
+        let episode_id = ep_info.episode_id;
+        let mut current = downloaded;
+        let mut join_set = JoinSet::new();
+        // 下载图片，同时下载的图片数量由配置决定
         for (i, path_url) in path_urls.into_iter().enumerate() {
-            let urls = vec![path_url.clone()];
-            let image_token_resp_data = match bili_client
-                .get_image_token(ep_info.comic_id, ep_info.episode_id, &urls)
-                .await
-            {
-                Ok(data) => data,
-                Err(err) => {
-                    let id = ep_info.episode_id;
-                    let err_msg = err.to_string_chain();
-                    emit_error_event(&self.app, id, path_url, err_msg);
-                    // 如果获取图片下载链接失败，则不再下载剩余的图片，直接跳出循环
+            if is_downloaded_flags[i] {
+                continue;
+            }
+            // 同时下载的图片数量达到上限时，等待其中一张图片下载完成
+            while join_set.len() >= self.image_concurrency() {
+                let Some(result) = join_set.join_next().await else {
                     break;
+                };
+                self.on_page_downloaded(episode_id, result, &mut current, &mut is_downloaded_flags);
+            }
+            // 如果任务被暂停，则等正在下载的图片完成后释放permit让其他章节下载，等任务恢复后再重新获取
+            if *state_receiver.borrow() == DownloadTaskState::Paused {
+                let is_cancelled = !self
+                    .join_pages(
+                        episode_id,
+                        &mut join_set,
+                        &mut state_receiver,
+                        &mut current,
+                        &mut is_downloaded_flags,
+                    )
+                    .await;
+                if is_cancelled {
+                    remove_temp_download_dir(&temp_download_dir);
+                    return;
                 }
-            };
+                permit = match self.wait_if_paused(permit, &mut state_receiver).await {
+                    Ok(Some(permit)) => permit,
+                    Ok(None) => {
+                        remove_temp_download_dir(&temp_download_dir);
+                        return;
+                    }
+                    Err(err) => {
+                        let err = err.context("获取下载章节的semaphore失败");
+                        let err_msg = err.to_string_chain();
+                        self.end_episode(&ep_info, None, Some(err_msg), vec![]);
+                        return;
+                    }
+                };
+            }
+            // 如果任务被取消，则中止正在下载的图片，等它们结束后再删除临时下载目录
+            if *state_receiver.borrow() == DownloadTaskState::Cancelled {
+                join_set.abort_all();
+                while join_set.join_next().await.is_some() {}
+                remove_temp_download_dir(&temp_download_dir);
+                return;
+            }
             let save_path = temp_download_dir.join(format!("{:03}.jpg", i + 1));
-            // 构造图片下载链接
-            let url = &image_token_resp_data[0].complete_url;
-            // 下载图片
-            if let Err(err) = self.download_image(url, &save_path).await {
-                let id = ep_info.episode_id;
+            let download_page_task =
+                self.clone()
+                    .download_page(token_cache.clone(), i, path_url, save_path);
+            join_set.spawn(download_page_task);
+            // 每开始下载一张图片，都休息一段时间
+            tokio::time::sleep(self.image_interval()).await;
+        }
+        // 等待剩余的图片下载完成，期间任务被取消则不再保存
+        let is_cancelled = !self
+            .join_pages(
+                episode_id,
+                &mut join_set,
+                &mut state_receiver,
+                &mut current,
+                &mut is_downloaded_flags,
+            )
+            .await;
+        if is_cancelled {
+            remove_temp_download_dir(&temp_download_dir);
+            return;
+        }
+        // 任务在等待期间被暂停，则释放permit让其他章节下载，等任务恢复后再继续保存
+        let permit = match self.wait_if_paused(permit, &mut state_receiver).await {
+            Ok(Some(permit)) => permit,
+            Ok(None) => {
+                remove_temp_download_dir(&temp_download_dir);
+                return;
+            }
+            Err(err) => {
+                let err = err.context("获取下载章节的semaphore失败");
                 let err_msg = err.to_string_chain();
-                emit_error_event(&self.app, id, url.clone(), err_msg);
-                // 如果下载失败，则不再下载剩余的图片，直接跳出循环
-                break;
+                self.end_episode(&ep_info, None, Some(err_msg), vec![]);
+                return;
             }
-            // 下载完成后，更新章节下载进度
-            current += 1;
-            emit_success_event(
-                &self.app,
-                ep_info.episode_id,
-                save_path.to_string_lossy().to_string(), // TODO: 把save_path.to_string_lossy().to_string()保存到一个变量里，像current一样
-                current,
-            );
-            // 每下载完一张图片，都休息300-800ms
-            let sleep_time = rand::thread_rng().gen_range(300..=800);
-            tokio::time::sleep(Duration::from_millis(sleep_time)).await;
-        }
+        };
         // 该章节的图片下载完成，释放permit，允许其他章节下载
         drop(permit);
         // 检查此章节的图片是否全部下载成功
+        let failed_pages: Vec<u32> = is_downloaded_flags
+            .iter()
+            .enumerate()
+            .filter(|(_, is_downloaded)| !**is_downloaded)
+            .map(|(i, _)| i as u32 + 1)
+            .collect();
         // 此章节的图片未全部下载成功
-        if current != total {
-            let err_msg = Some(format!("总共有 {total} 张图片，但只下载了 {current} 张"));
-            emit_end_event(&self.app, ep_info.episode_id, err_msg);
+        if !failed_pages.is_empty() {
+            let pages = failed_pages
+                .iter()
+                .map(u32::to_string)
+                .collect::<Vec<String>>()
+                .join(", ");
+            let err_msg = Some(format!(
+                "总共有 {total} 张图片，但只下载了 {current} 张，下载失败的页码: {pages}"
+            ));
+            self.end_episode(&ep_info, None, err_msg, failed_pages);
             return;
         }
-        // 此章节的图片全部下载成功，保存图片
-        let err_msg = match self.save_archive(&ep_info, &temp_download_dir) {
-            Ok(_) => None,
-            Err(err) => Some(err.to_string_chain()),
+        // 此章节的图片全部下载成功，按配置先去除水印再保存图片
+        if self.remove_watermark_before_archive() {
+            let app = self.app.clone();
+            let dir_path = temp_download_dir.clone();
+            let result = tauri::async_runtime::spawn_blocking(move || {
+                watermark::remove_watermark(&app, &dir_path)
+            })
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|result| result);
+            if let Err(err) = result {
+                let err = err.context(format!("去除 {temp_download_dir:?} 中图片的水印失败"));
+                self.end_episode(&ep_info, None, Some(err.to_string_chain()), vec![]);
+                return;
+            }
+        }
+        // 按配置将章节封面作为第0页，封面下载失败时不影响章节的保存
+        let mut ep_info = ep_info;
+        let mut page_sizes = page_sizes;
+        if self.should_prepend_episode_cover()
+            && self
+                .prepend_episode_cover(&ep_info, &temp_download_dir)
+                .await
+        {
+            ep_info.comic_info.page_count += 1;
+            page_sizes.insert(0, PageSize::default());
+        }
+        // 去除水印和下载封面期间任务可能被取消，此时前端已经移除了该任务，不应再保存
+        if *state_receiver.borrow() == DownloadTaskState::Cancelled {
+            remove_temp_download_dir(&temp_download_dir);
+            return;
+        }
+        match self
+            .save_archive(&ep_info, &temp_download_dir, &page_sizes)
+            .await
+        {
+            Ok(path) => {
+                let archive_format = self
+                    .app
+                    .state::<RwLock<Config>>()
+                    .read()
+                    .archive_format
+                    .clone();
+                let library = self.app.state::<Library>();
+                library.index_archive(&path, &archive_format);
+                library.remove_corrupted(&path, &archive_format);
+                if self.save_comic_cover() {
+                    self.save_comic_cover_to_dir(&ep_info, &path).await;
+                }
+                if self.save_series_metadata() {
+                    self.save_series_metadata_to_dir(&ep_info, &path).await;
+                }
+                self.end_episode(&ep_info, Some(path), None, vec![]);
+            }
+            Err(err) => self.end_episode(&ep_info, None, Some(err.to_string_chain()), vec![]),
+        }
+    }
+
+    /// 下载章节中的第 `page_index + 1` 张图片，下载成功时返回图片的页码索引和保存路径
+    ///
+    /// 每次下载失败都会按配置等待一段时间后重试，重试次数用完后发送 `DownloadImageErrorEvent` 并返回 `None`
+    async fn download_page(
+        self,
+        token_cache: Arc<ImageTokenCache>,
+        page_index: usize,
+        path_url: String,
+        save_path: PathBuf,
+    ) -> Option<(usize, PathBuf)> {
+        let (retry_attempts, retry_backoff_ms) = {
+            let config = self.app.state::<RwLock<Config>>();
+            let config = config.read();
+            (config.image_retry_attempts, config.image_retry_backoff_ms)
         };
-        emit_end_event(&self.app, ep_info.episode_id, err_msg);
+        let mut attempt = 0;
+        loop {
+            let Err(err) = self
+                .try_download_page(&token_cache, &path_url, &save_path)
+                .await
+            else {
+                return Some((page_index, save_path));
+            };
+            if attempt >= retry_attempts {
+                let err = err.context(format!(
+                    "第 {} 张图片重试了 {attempt} 次仍然下载失败",
+                    page_index + 1
+                ));
+                let err_msg = err.to_string_chain();
+                emit_error_event(&self.app, token_cache.episode_id, path_url, err_msg);
+                return None;
+            }
+            // 每次重试前的等待时间都是上一次的两倍
+            let backoff_ms = retry_backoff_ms.saturating_mul(1 << attempt.min(16));
+            tokio::time::sleep(Duration::from_millis(backoff_ms)).await;
+            attempt += 1;
+        }
     }
 
-    fn save_archive(
+    async fn try_download_page(
         &self,
-        ep_info: &EpisodeInfo,
-        temp_download_dir: &PathBuf,
+        token_cache: &ImageTokenCache,
+        path_url: &str,
+        save_path: &Path,
     ) -> anyhow::Result<()> {
-        let archive_format = self
-            .app
+        let bili_client = self.bili_client();
+        let batch_size = self.image_token_batch_size();
+        let url = token_cache
+            .get_complete_url(&bili_client, path_url, batch_size)
+            .await
+            .context(format!("获取图片 {path_url} 的下载链接失败"))?;
+        let episode_id = token_cache.episode_id;
+        match self.download_image(episode_id, &url, save_path).await {
+            // 下载时返回403说明ImageToken已过期，此时重新获取ImageToken后立即再试一次
+            Err(err) if is_forbidden_error(&err) => {
+                token_cache.invalidate(path_url, &url).await;
+                let url = token_cache
+                    .get_complete_url(&bili_client, path_url, batch_size)
+                    .await
+                    .context(format!("重新获取图片 {path_url} 的下载链接失败"))?;
+                self.download_image(episode_id, &url, save_path).await
+            }
+            result => result,
+        }
+    }
+
+    /// 处理下载结束的图片，下载成功则更新章节下载进度
+    fn on_page_downloaded(
+        &self,
+        episode_id: i64,
+        result: Result<Option<(usize, PathBuf)>, JoinError>,
+        current: &mut u32,
+        is_downloaded_flags: &mut [bool],
+    ) {
+        let Ok(Some((page_index, save_path))) = result else {
+            return;
+        };
+        is_downloaded_flags[page_index] = true;
+        *current += 1;
+        self.update_progress(episode_id, |progress| {
+            progress.downloaded_pages.fetch_add(1, Ordering::Relaxed);
+            progress.fetched_pages.fetch_add(1, Ordering::Relaxed);
+        });
+        let save_path = save_path.to_string_lossy().to_string();
+        emit_success_event(&self.app, episode_id, save_path, *current);
+    }
+
+    /// 调整同时下载的章节数量上限
+    ///
+    /// 空闲的permit会被立即丢弃，正在下载的章节占用的permit无法立即收回，由 `EpPermit` 在释放时丢弃
+    pub fn set_episode_concurrency(&self, concurrency: usize) {
+        let mut count = self.ep_permit_count.lock();
+        count.target = concurrency.max(1);
+        if count.total < count.target {
+            self.ep_sem.add_permits(count.target - count.total);
+            count.total = count.target;
+        }
+        while count.total > count.target {
+            let Ok(permit) = self.ep_sem.try_acquire() else {
+                break;
+            };
+            permit.forget();
+            count.total -= 1;
+        }
+    }
+
+    fn remove_watermark_before_archive(&self) -> bool {
+        self.app
             .state::<RwLock<Config>>()
             .read()
-            .archive_format
-            .clone();
+            .remove_watermark_before_archive
+    }
 
-        let Some(parent) = temp_download_dir.parent() else {
-            let err_msg = Some(format!("无法获取 {temp_download_dir:?} 的父目录"));
-            emit_end_event(&self.app, ep_info.episode_id, err_msg);
-            return Ok(());
-        };
+    fn save_series_metadata(&self) -> bool {
+        self.app
+            .state::<RwLock<Config>>()
+            .read()
+            .save_series_metadata
+    }
 
-        let download_dir = parent.join(&ep_info.episode_title);
-        // TODO: 把每种格式的保存操作提取到一个函数里
-        match archive_format {
-            ArchiveFormat::Image => {
-                if download_dir.exists() {
-                    std::fs::remove_dir_all(&download_dir)
-                        .context(format!("删除 {download_dir:?} 失败"))?;
-                }
+    fn save_comic_cover(&self) -> bool {
+        self.app.state::<RwLock<Config>>().read().save_comic_cover
+    }
+
+    /// 只有cbz才会在最前面加上章节封面
+    fn should_prepend_episode_cover(&self) -> bool {
+        let config = self.app.state::<RwLock<Config>>();
+        let config = config.read();
+        config.prepend_episode_cover && config.archive_format == ArchiveFormat::Cbz
+    }
 
-                std::fs::rename(temp_download_dir, &download_dir).context(format!(
-                    "将 {temp_download_dir:?} 重命名为 {download_dir:?} 失败"
-                ))?;
+    fn image_token_batch_size(&self) -> usize {
+        let config = self.app.state::<RwLock<Config>>();
+        let image_token_batch_size = config.read().image_token_batch_size;
+        image_token_batch_size.max(1)
+    }
+
+    fn image_concurrency(&self) -> usize {
+        let config = self.app.state::<RwLock<Config>>();
+        let image_concurrency = config.read().image_concurrency;
+        image_concurrency.max(1)
+    }
+
+    /// 在配置的范围内随机生成每张图片之间的下载间隔
+    fn image_interval(&self) -> Duration {
+        let config = self.app.state::<RwLock<Config>>();
+        let config = config.read();
+        let min = config.image_interval_min_ms;
+        let max = config.image_interval_max_ms.max(min);
+        Duration::from_millis(rand::thread_rng().gen_range(min..=max))
+    }
+
+    /// 获取下载章节的permit，如果任务处于暂停状态，则等待任务恢复后再获取
+    ///
+    /// 如果任务在等待期间被取消，则返回 `None`
+    async fn acquire_ep_permit(
+        &self,
+        state_receiver: &mut watch::Receiver<DownloadTaskState>,
+    ) -> anyhow::Result<Option<EpPermit<'_>>> {
+        loop {
+            let state = *state_receiver
+                .wait_for(|state| *state != DownloadTaskState::Paused)
+                .await?;
+            if state == DownloadTaskState::Cancelled {
+                return Ok(None);
             }
-            ArchiveFormat::Cbz | ArchiveFormat::Zip => {
-                let comic_info_path = temp_download_dir.join("ComicInfo.xml");
-                let comic_info_xml = yaserde::ser::to_string(&ep_info.comic_info)
-                    .map_err(|err_msg| anyhow!("序列化 {comic_info_path:?} 失败: {err_msg}"))?;
-                std::fs::write(&comic_info_path, comic_info_xml)
-                    .context(format!("创建 {comic_info_path:?} 失败"))?;
-
-                let zip_path = download_dir.with_extension(archive_format.extension());
-                let zip_file =
-                    File::create(&zip_path).context(format!("创建 {zip_path:?} 失败"))?;
-
-                let mut zip_writer = ZipWriter::new(zip_file);
-
-                for entry in std::fs::read_dir(temp_download_dir)?.filter_map(Result::ok) {
-                    let path = entry.path();
-                    if !path.is_file() {
-                        continue;
-                    }
+            // 等待permit的同时监听任务状态，以便在等待期间也能响应暂停和取消
+            tokio::select! {
+                permit = self.ep_sem.acquire() => {
+                    return Ok(Some(EpPermit {
+                        permit: Some(permit?),
+                        count: &self.ep_permit_count,
+                    }));
+                }
+                changed = state_receiver.changed() => changed?,
+            }
+        }
+    }
 
-                    let filename = match path.file_name() {
-                        Some(name) => name.to_string_lossy(),
-                        None => continue,
+    /// 等待 `join_set` 中正在下载的图片全部结束，期间任务被取消则中止它们并返回 `false`
+    async fn join_pages(
+        &self,
+        episode_id: i64,
+        join_set: &mut JoinSet<Option<(usize, PathBuf)>>,
+        state_receiver: &mut watch::Receiver<DownloadTaskState>,
+        current: &mut u32,
+        is_downloaded_flags: &mut [bool],
+    ) -> bool {
+        if *state_receiver.borrow() == DownloadTaskState::Cancelled {
+            join_set.abort_all();
+        }
+        loop {
+            tokio::select! {
+                result = join_set.join_next() => {
+                    let Some(result) = result else {
+                        return *state_receiver.borrow() != DownloadTaskState::Cancelled;
                     };
+                    self.on_page_downloaded(episode_id, result, current, is_downloaded_flags);
+                }
+                Ok(()) = state_receiver.changed() => {
+                    if *state_receiver.borrow() == DownloadTaskState::Cancelled {
+                        join_set.abort_all();
+                    }
+                }
+            }
+        }
+    }
 
-                    zip_writer
-                        .start_file(&filename, SimpleFileOptions::default())
-                        .context(format!("在 {zip_path:?} 创建 {filename:?} 失败"))?;
+    /// 任务被暂停时释放 `permit` 让其他章节下载，等任务恢复后再重新获取，任务在此期间被取消时返回 `Ok(None)`
+    async fn wait_if_paused<'a>(
+        &'a self,
+        permit: EpPermit<'a>,
+        state_receiver: &mut watch::Receiver<DownloadTaskState>,
+    ) -> anyhow::Result<Option<EpPermit<'a>>> {
+        match *state_receiver.borrow() {
+            DownloadTaskState::Paused => {}
+            DownloadTaskState::Cancelled => return Ok(None),
+            DownloadTaskState::Running => return Ok(Some(permit)),
+        }
+        drop(permit);
+        self.acquire_ep_permit(state_receiver).await
+    }
 
-                    let mut file = File::open(&path).context(format!("打开 {path:?} 失败"))?;
+    /// 按配置的格式保存章节，返回保存的路径
+    async fn save_archive(
+        &self,
+        ep_info: &EpisodeInfo,
+        temp_download_dir: &PathBuf,
+        page_sizes: &[PageSize],
+    ) -> anyhow::Result<PathBuf> {
+        let (archive_format, compression, compression_level) = {
+            let config = self.app.state::<RwLock<Config>>();
+            let config = config.read();
+            (
+                config.archive_format.clone(),
+                config.archive_compression.clone(),
+                config.archive_compression_level,
+            )
+        };
 
-                    std::io::copy(&mut file, &mut zip_writer)
-                        .context(format!("将 {path:?} 写入 {zip_path:?} 失败"))?;
-                }
+        let Some(parent) = temp_download_dir.parent() else {
+            return Err(anyhow!("无法获取 {temp_download_dir:?} 的父目录"));
+        };
+
+        let archive_path =
+            archive::get_archive_path(parent, &ep_info.episode_title, &archive_format);
+        match archive_format {
+            ArchiveFormat::Image => {
+                archive::save_image_dir(temp_download_dir, &archive_path)?;
+                Ok(archive_path)
+            }
+            ArchiveFormat::Cbz | ArchiveFormat::Zip => {
+                archive::save_zip(
+                    temp_download_dir,
+                    &ep_info.comic_info,
+                    &archive_path,
+                    &compression,
+                    compression_level,
+                    page_sizes,
+                )?;
+                Ok(archive_path)
+            }
+            ArchiveFormat::Pdf => {
+                pdf::save_pdf(
+                    temp_download_dir,
+                    &ep_info.comic_info,
+                    &archive_path,
+                    page_sizes,
+                )?;
+                Ok(archive_path)
+            }
+            ArchiveFormat::Epub => {
+                let metadata = EpubMetadata {
+                    identifier: format!(
+                        "bilibili-manga-{}-{}",
+                        ep_info.comic_id, ep_info.episode_id
+                    ),
+                    comic_info: ep_info.comic_info.clone(),
+                    cover: self.download_cover(&ep_info.vertical_cover).await,
+                    is_right_to_left: ep_info.is_right_to_left,
+                };
+                let options = archive::zip_file_options(&compression, compression_level);
+                epub::save_epub(
+                    temp_download_dir,
+                    &metadata,
+                    &archive_path,
+                    page_sizes,
+                    options,
+                )?;
+                Ok(archive_path)
+            }
+        }
+    }
+
+    /// 下载章节封面并保存为 `temp_download_dir` 中的 `000.jpg`，返回是否保存成功
+    async fn prepend_episode_cover(&self, ep_info: &EpisodeInfo, temp_download_dir: &Path) -> bool {
+        let Some(cover_data) = self.download_cover(&ep_info.episode_cover).await else {
+            return false;
+        };
+        let cover_path = temp_download_dir.join("000.jpg");
+        std::fs::write(cover_path, cover_data).is_ok()
+    }
 
-                zip_writer
-                    .finish()
-                    .context(format!("关闭 {zip_path:?} 失败"))?;
+    /// 下载漫画封面并保存为漫画目录中的 `cover.jpg`，已经存在时不会重复下载
+    async fn save_comic_cover_to_dir(&self, ep_info: &EpisodeInfo, archive_path: &Path) {
+        let Some(comic_dir) = archive_path.parent() else {
+            return;
+        };
+        let cover_path = comic_dir.join("cover.jpg");
+        if cover_path.exists() {
+            return;
+        }
+        let Some(cover_data) = self.download_cover(&ep_info.vertical_cover).await else {
+            return;
+        };
+        // 封面只是锦上添花，保存失败也无需通知前端
+        let _ = archive::write_atomically(&cover_path, |temp_cover_path| {
+            std::fs::write(temp_cover_path, &cover_data)
+                .context(format!("写入 {temp_cover_path:?} 失败"))
+        });
+    }
 
-                std::fs::remove_dir_all(temp_download_dir)
-                    .context(format!("删除 {temp_download_dir:?} 失败"))?;
+    /// 漫画目录中还没有系列元数据时(例如第一次下载该漫画)，获取漫画信息并保存系列元数据
+    ///
+    /// 已经存在时不会重复获取，之后的更新由浏览漫画时的 `get_comic` 负责
+    async fn save_series_metadata_to_dir(&self, ep_info: &EpisodeInfo, archive_path: &Path) {
+        let Some(comic_dir) = archive_path.parent() else {
+            return;
+        };
+        if comic_dir.join("series.json").exists() {
+            return;
+        }
+        match self.bili_client().get_comic(ep_info.comic_id).await {
+            Ok(comic) => series_metadata::try_save_series_metadata(&self.app, &comic),
+            Err(err) => {
+                let comic_title = ep_info.comic_title.clone();
+                let err = err.context(format!("获取 {comic_title} 的信息失败"));
+                series_metadata::emit_error_event(&self.app, comic_title, err.to_string_chain());
             }
         }
-        Ok(())
     }
 
-    async fn download_image(&self, url: &str, save_path: &Path) -> anyhow::Result<()> {
+    /// 下载封面，封面只是锦上添花，下载失败时返回 `None` 而不是让整个章节保存失败
+    async fn download_cover(&self, cover_url: &str) -> Option<Vec<u8>> {
+        if cover_url.is_empty() {
+            return None;
+        }
+        let cover_data = self.bili_client().get_image_bytes(cover_url).await.ok()?;
+        Some(cover_data.to_vec())
+    }
+
+    async fn download_image(
+        &self,
+        episode_id: i64,
+        url: &str,
+        save_path: &Path,
+    ) -> anyhow::Result<()> {
         let image_data = self
             .bili_client()
             .get_image_bytes(url)
@@ -305,26 +1046,246 @@ impl DownloadManager {
         // 保存图片
         std::fs::write(save_path, &image_data).context(format!("保存图片 {save_path:?} 失败"))?;
         // 记录下载字节数
-        self.byte_per_sec
-            .fetch_add(image_data.len() as u64, Ordering::Relaxed);
+        let image_size = image_data.len() as u64;
+        self.byte_per_sec.fetch_add(image_size, Ordering::Relaxed);
+        self.update_progress(episode_id, |progress| {
+            progress
+                .downloaded_bytes
+                .fetch_add(image_size, Ordering::Relaxed);
+            progress
+                .byte_per_sec
+                .fetch_add(image_size, Ordering::Relaxed);
+        });
         Ok(())
     }
 
+    /// 发送下载结束事件，并将下载结果记录到下载历史中
+    fn end_episode(
+        &self,
+        ep_info: &EpisodeInfo,
+        path: Option<PathBuf>,
+        err_msg: Option<String>,
+        failed_pages: Vec<u32>,
+    ) {
+        let (page_count, downloaded_bytes, started_at) = self
+            .tasks
+            .read()
+            .get(&ep_info.episode_id)
+            .map(|entry| {
+                let progress = &entry.progress;
+                (
+                    progress.total_pages.load(Ordering::Relaxed),
+                    progress.downloaded_bytes.load(Ordering::Relaxed),
+                    progress.started_at.load(Ordering::Relaxed),
+                )
+            })
+            .unwrap_or_default();
+        let bytes = path.as_deref().map_or(downloaded_bytes, utils::path_size);
+        let archive_format = self
+            .app
+            .state::<RwLock<Config>>()
+            .read()
+            .archive_format
+            .clone();
+        let entry = DownloadHistoryEntry {
+            comic_id: ep_info.comic_id,
+            comic_title: ep_info.comic_title.clone(),
+            episode_id: ep_info.episode_id,
+            episode_title: ep_info.episode_title.clone(),
+            archive_format,
+            path,
+            page_count,
+            bytes,
+            started_at: (started_at != 0).then_some(started_at),
+            finished_at: chrono::Local::now().timestamp(),
+            err_msg: err_msg.clone(),
+            failed_pages: failed_pages.clone(),
+        };
+        // 下载历史只用于查询，记录失败不影响下载结果
+        if let Err(err) = self.app.state::<DownloadHistory>().record(&entry) {
+            let comic_title = &ep_info.comic_title;
+            let episode_title = &ep_info.episode_title;
+            let err = err.context(format!(
+                "记录 {comic_title} - {episode_title} 的下载历史失败"
+            ));
+            emit_record_history_error_event(&self.app, err.to_string_chain());
+        }
+        emit_end_event(&self.app, ep_info.episode_id, err_msg, failed_pages);
+    }
+
+    fn update_progress(&self, episode_id: i64, update: impl FnOnce(&EpisodeProgress)) {
+        if let Some(entry) = self.tasks.read().get(&episode_id) {
+            update(&entry.progress);
+        }
+    }
+
     fn bili_client(&self) -> BiliClient {
         self.app.state::<BiliClient>().inner().clone()
     }
 }
 
+/// `ep_sem` 中permit的数量，所有对permit总数的修改都在锁内进行，保证总数最终与配置的并发数一致
+struct EpPermitCount {
+    /// 配置的并发数
+    target: usize,
+    /// `ep_sem` 中包括已被占用的permit在内的permit总数
+    total: usize,
+}
+
+/// 下载章节的permit，释放时如果permit总数超过了配置的并发数，则丢弃该permit而不是归还
+struct EpPermit<'a> {
+    permit: Option<SemaphorePermit<'a>>,
+    count: &'a Mutex<EpPermitCount>,
+}
+
+impl Drop for EpPermit<'_> {
+    fn drop(&mut self) {
+        let mut count = self.count.lock();
+        if let Some(permit) = self.permit.take() {
+            if count.total > count.target {
+                permit.forget();
+                count.total -= 1;
+            }
+        }
+    }
+}
+
+/// 同一章节内共享的 `ImageToken` 缓存
+///
+/// 按批次获取 `ImageToken` 以减少请求次数，只有在 `ImageToken` 过期或下载时返回403才会重新获取
+struct ImageTokenCache {
+    comic_id: i64,
+    episode_id: i64,
+    /// 需要下载的图片的path，按页码排序
+    path_urls: Vec<String>,
+    tokens: tokio::sync::Mutex<HashMap<String, CachedImageToken>>,
+}
+
+struct CachedImageToken {
+    complete_url: String,
+    fetched_at: Instant,
+}
+
+impl CachedImageToken {
+    fn is_expired(&self) -> bool {
+        self.fetched_at.elapsed() >= IMAGE_TOKEN_TTL
+    }
+}
+
+impl ImageTokenCache {
+    fn new(comic_id: i64, episode_id: i64, path_urls: Vec<String>) -> Self {
+        Self {
+            comic_id,
+            episode_id,
+            path_urls,
+            tokens: tokio::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 获取图片的下载链接
+    ///
+    /// 如果缓存中没有 `path_url` 的有效 `ImageToken`，则从 `path_url` 开始，
+    /// 为最多 `batch_size` 张没有有效 `ImageToken` 的图片一次性获取 `ImageToken`
+    async fn get_complete_url(
+        &self,
+        bili_client: &BiliClient,
+        path_url: &str,
+        batch_size: usize,
+    ) -> anyhow::Result<String> {
+        // 获取ImageToken期间一直持有锁，避免多张图片同时请求同一批ImageToken
+        let mut tokens = self.tokens.lock().await;
+        if let Some(token) = tokens.get(path_url).filter(|token| !token.is_expired()) {
+            return Ok(token.complete_url.clone());
+        }
+
+        let batch: Vec<String> = match self.path_urls.iter().position(|url| url == path_url) {
+            Some(start) => self.path_urls[start..]
+                .iter()
+                .filter(|url| tokens.get(*url).is_none_or(CachedImageToken::is_expired))
+                .take(batch_size)
+                .cloned()
+                .collect(),
+            None => vec![path_url.to_string()],
+        };
+        let image_token_resp_data = bili_client
+            .get_image_token(self.comic_id, self.episode_id, &batch)
+            .await?;
+        if image_token_resp_data.len() != batch.len() {
+            return Err(anyhow!(
+                "获取ImageToken失败，请求了 {} 个ImageToken，但只返回了 {} 个",
+                batch.len(),
+                image_token_resp_data.len()
+            ));
+        }
+        // batch的第一个元素就是path_url
+        let complete_url = image_token_resp_data[0].complete_url.clone();
+        let fetched_at = Instant::now();
+        for (url, item) in batch.into_iter().zip(image_token_resp_data) {
+            let token = CachedImageToken {
+                complete_url: item.complete_url,
+                fetched_at,
+            };
+            tokens.insert(url, token);
+        }
+
+        Ok(complete_url)
+    }
+
+    /// 下载 `path_url` 时返回403，说明它所在批次的 `ImageToken` 都已经失效，将它们从缓存中移除
+    ///
+    /// 如果缓存中的下载链接已经不是 `failed_complete_url`，说明已经被其他图片刷新过，无需移除
+    async fn invalidate(&self, path_url: &str, failed_complete_url: &str) {
+        let mut tokens = self.tokens.lock().await;
+        let Some(failed_token) = tokens.get(path_url) else {
+            return;
+        };
+        if failed_token.complete_url != failed_complete_url {
+            return;
+        }
+        let fetched_at = failed_token.fetched_at;
+        tokens.retain(|_, token| token.fetched_at > fetched_at);
+    }
+}
+
+/// 检查错误是否由403状态码引起，这通常意味着 `ImageToken` 已经失效
+fn is_forbidden_error(err: &anyhow::Error) -> bool {
+    err.chain()
+        .filter_map(|err| err.downcast_ref::<reqwest::Error>())
+        .any(|err| err.status() == Some(StatusCode::FORBIDDEN))
+}
+
 fn get_ep_temp_download_dir(app: &AppHandle, ep_info: &EpisodeInfo) -> PathBuf {
     app.state::<RwLock<Config>>()
         .read()
         .download_dir
         .join(&ep_info.comic_title)
-        .join(format!(".下载中-{}", ep_info.episode_title)) // 以 `.下载中-` 开头，表示是临时目录
+        .join(format!("{DOWNLOADING_DIR_PREFIX}{}", ep_info.episode_title))
 }
 
-fn emit_start_event(app: &AppHandle, id: i64, total: u32) {
-    let payload = events::DownloadStartEventPayload { id, total };
+/// 检查 `path` 是否为能够正常解码的图片
+fn is_valid_image(path: &Path) -> bool {
+    let Ok(reader) = image::ImageReader::open(path) else {
+        return false;
+    };
+    let Ok(reader) = reader.with_guessed_format() else {
+        return false;
+    };
+    reader.decode().is_ok()
+}
+
+fn remove_temp_download_dir(temp_download_dir: &Path) {
+    if temp_download_dir.exists() {
+        // 任务已经被取消，即使删除失败也无需再通知前端
+        let _ = std::fs::remove_dir_all(temp_download_dir);
+    }
+}
+
+fn emit_start_event(app: &AppHandle, id: i64, total: u32, downloaded: u32) {
+    let payload = events::DownloadStartEventPayload {
+        id,
+        total,
+        downloaded,
+    };
     let event = events::DownloadStartEvent(payload);
     let _ = event.emit(app);
 }
@@ -351,14 +1312,58 @@ fn emit_error_event(app: &AppHandle, id: i64, url: String, err_msg: String) {
     let _ = event.emit(app);
 }
 
-fn emit_end_event(app: &AppHandle, id: i64, err_msg: Option<String>) {
-    let payload = events::DownloadEndEventPayload { id, err_msg };
+fn emit_end_event(app: &AppHandle, id: i64, err_msg: Option<String>, failed_pages: Vec<u32>) {
+    let payload = events::DownloadEndEventPayload {
+        id,
+        err_msg,
+        failed_pages,
+    };
     let event = events::DownloadEndEvent(payload);
     let _ = event.emit(app);
 }
 
-fn emit_download_speed_event(app: &AppHandle, speed: String) {
-    let payload = DownloadSpeedEventPayload { speed };
+fn emit_pause_event(app: &AppHandle, id: i64) {
+    let payload = events::DownloadPauseEventPayload { id };
+    let event = events::DownloadPauseEvent(payload);
+    let _ = event.emit(app);
+}
+
+fn emit_resume_event(app: &AppHandle, id: i64) {
+    let payload = events::DownloadResumeEventPayload { id };
+    let event = events::DownloadResumeEvent(payload);
+    let _ = event.emit(app);
+}
+
+fn emit_cancel_event(app: &AppHandle, id: i64) {
+    let payload = events::DownloadCancelEventPayload { id };
+    let event = events::DownloadCancelEvent(payload);
+    let _ = event.emit(app);
+}
+
+fn emit_restore_tasks_error_event(app: &AppHandle, err_msg: String) {
+    let payload = events::RestoreDownloadTasksErrorEventPayload { err_msg };
+    let event = events::RestoreDownloadTasksErrorEvent(payload);
+    let _ = event.emit(app);
+}
+
+fn emit_record_history_error_event(app: &AppHandle, err_msg: String) {
+    let payload = events::RecordDownloadHistoryErrorEventPayload { err_msg };
+    let event = events::RecordDownloadHistoryErrorEvent(payload);
+    let _ = event.emit(app);
+}
+
+/// 根据剩余字节数和下载速度估计剩余秒数，速度为0时无法估计，返回 `None`
+fn estimate_eta_secs(remaining_bytes: u64, bytes_per_sec: u64) -> Option<u64> {
+    if remaining_bytes == 0 {
+        return Some(0);
+    }
+    if bytes_per_sec == 0 {
+        return None;
+    }
+    Some(remaining_bytes.div_ceil(bytes_per_sec))
+}
+
+fn emit_download_speed_event(app: &AppHandle, payload: DownloadSpeedEventPayload) {
     let event = DownloadSpeedEvent(payload);
     let _ = event.emit(app);
 }
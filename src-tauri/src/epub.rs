@@ -0,0 +1,448 @@
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use image::{ImageFormat, ImageReader};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, DateTime, ZipArchive, ZipWriter};
+
+use crate::archive;
+use crate::archive::PageSize;
+use crate::types::ComicInfo;
+use crate::utils::escape_xml;
+
+const CONTAINER_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+/// 将 `temp_download_dir` 中的图片保存为固定版式的 `epub_path`，保存成功后删除 `temp_download_dir`
+pub fn save_epub(
+    temp_download_dir: &Path,
+    metadata: &EpubMetadata,
+    epub_path: &Path,
+    page_sizes: &[PageSize],
+    options: SimpleFileOptions,
+) -> anyhow::Result<()> {
+    let page_paths = archive::get_sorted_page_paths(temp_download_dir)?;
+    let mut epub_builder = EpubBuilder::new(metadata);
+    epub_builder.add_chapter(&metadata.comic_info.title, &page_paths, page_sizes)?;
+    epub_builder.save(epub_path, options)?;
+
+    std::fs::remove_dir_all(temp_download_dir)
+        .context(format!("删除 {temp_download_dir:?} 失败"))?;
+    Ok(())
+}
+
+/// 读取 `epub_path` 的 `content.opf` 中的翻页方向，返回是否从右往左阅读
+pub fn read_is_right_to_left(epub_path: &Path) -> anyhow::Result<bool> {
+    let epub_file = File::open(epub_path).context(format!("打开 {epub_path:?} 失败"))?;
+    let mut zip_archive = ZipArchive::new(epub_file).context(format!("读取 {epub_path:?} 失败"))?;
+    let mut content_opf = String::new();
+    zip_archive
+        .by_name("OEBPS/content.opf")
+        .context(format!("{epub_path:?} 中没有 `OEBPS/content.opf`"))?
+        .read_to_string(&mut content_opf)
+        .context(format!("读取 {epub_path:?} 中的 `OEBPS/content.opf` 失败"))?;
+    Ok(content_opf.contains(r#"page-progression-direction="rtl""#))
+}
+
+pub struct EpubMetadata {
+    /// 书籍的唯一标识符
+    pub identifier: String,
+    pub comic_info: ComicInfo,
+    /// 封面图片的原始数据，为 `None` 时不包含封面
+    pub cover: Option<Vec<u8>>,
+    pub is_right_to_left: bool,
+}
+
+struct EpubPage {
+    src_path: PathBuf,
+    /// 页码，从1开始
+    number: usize,
+    extension: String,
+    media_type: String,
+    size: PageSize,
+}
+
+/// 按章节逐页构建固定版式的EPUB 3，每张图片占一页
+pub struct EpubBuilder<'a> {
+    metadata: &'a EpubMetadata,
+    pages: Vec<EpubPage>,
+    /// 章节标题和章节第一页的页码
+    chapters: Vec<(String, usize)>,
+}
+
+impl<'a> EpubBuilder<'a> {
+    pub fn new(metadata: &'a EpubMetadata) -> Self {
+        Self {
+            metadata,
+            pages: Vec::new(),
+            chapters: Vec::new(),
+        }
+    }
+
+    /// 添加一个章节，页面尺寸优先使用 `page_sizes` 中对应的尺寸，未知时使用图片本身的尺寸
+    pub fn add_chapter(
+        &mut self,
+        title: &str,
+        page_paths: &[PathBuf],
+        page_sizes: &[PageSize],
+    ) -> anyhow::Result<()> {
+        if page_paths.is_empty() {
+            return Ok(());
+        }
+        self.chapters
+            .push((title.to_string(), self.pages.len() + 1));
+        for (i, page_path) in page_paths.iter().enumerate() {
+            let mut size = page_sizes.get(i).copied().unwrap_or_default();
+            if size.width == 0 || size.height == 0 {
+                let (width, height) = image::image_dimensions(page_path)
+                    .context(format!("读取图片 {page_path:?} 的尺寸失败"))?;
+                size = PageSize { width, height };
+            }
+            let format = ImageFormat::from_path(page_path).unwrap_or(ImageFormat::Jpeg);
+            self.pages.push(EpubPage {
+                src_path: page_path.clone(),
+                number: self.pages.len() + 1,
+                extension: format.extensions_str()[0].to_string(),
+                media_type: format.to_mime_type().to_string(),
+                size,
+            });
+        }
+        Ok(())
+    }
+
+    /// 通过 `archive::write_atomically` 将EPUB写入 `epub_path`
+    pub fn save(&self, epub_path: &Path, options: SimpleFileOptions) -> anyhow::Result<()> {
+        archive::write_atomically(epub_path, |temp_epub_path| {
+            self.write(temp_epub_path, options)
+        })
+    }
+
+    fn write(&self, epub_path: &Path, options: SimpleFileOptions) -> anyhow::Result<()> {
+        let epub_file = File::create(epub_path).context(format!("创建 {epub_path:?} 失败"))?;
+        let mut zip_writer = ZipWriter::new(epub_file);
+        let cover = self.cover_image();
+
+        // `mimetype` 必须是第一个条目，并且不能被压缩
+        let mimetype_options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Stored)
+            .last_modified_time(DateTime::default())
+            .unix_permissions(0o644);
+        let mut entries = vec![
+            ("mimetype".to_string(), b"application/epub+zip".to_vec()),
+            ("META-INF/container.xml".to_string(), CONTAINER_XML.into()),
+            (
+                "OEBPS/content.opf".to_string(),
+                self.content_opf(cover.as_ref()).into(),
+            ),
+            ("OEBPS/nav.xhtml".to_string(), self.nav_xhtml().into()),
+        ];
+        if let Some(cover) = &cover {
+            let cover_data = self.metadata.cover.clone().unwrap_or_default();
+            entries.push((format!("OEBPS/images/{}", cover.file_name), cover_data));
+            let cover_xhtml = page_xhtml("封面", &cover.file_name, cover.size);
+            entries.push(("OEBPS/pages/cover.xhtml".to_string(), cover_xhtml.into()));
+        }
+        for page in &self.pages {
+            let img_name = page.img_name();
+            let title = format!("第{}页", page.number);
+            let xhtml = page_xhtml(&title, &img_name, page.size);
+            entries.push((format!("OEBPS/pages/{}", page.xhtml_name()), xhtml.into()));
+        }
+        for (i, (entry_name, data)) in entries.into_iter().enumerate() {
+            let entry_options = if i == 0 { mimetype_options } else { options };
+            zip_writer
+                .start_file(&entry_name, entry_options)
+                .context(format!("在 {epub_path:?} 创建 {entry_name} 失败"))?;
+            zip_writer
+                .write_all(&data)
+                .context(format!("将 {entry_name} 写入 {epub_path:?} 失败"))?;
+        }
+        // 图片直接从文件复制，避免将所有图片读入内存
+        for page in &self.pages {
+            let entry_name = format!("OEBPS/images/{}", page.img_name());
+            zip_writer
+                .start_file(&entry_name, options)
+                .context(format!("在 {epub_path:?} 创建 {entry_name} 失败"))?;
+            let src_path = &page.src_path;
+            let mut file = File::open(src_path).context(format!("打开 {src_path:?} 失败"))?;
+            std::io::copy(&mut file, &mut zip_writer)
+                .context(format!("将 {src_path:?} 写入 {epub_path:?} 失败"))?;
+        }
+
+        zip_writer
+            .finish()
+            .context(format!("关闭 {epub_path:?} 失败"))?;
+        Ok(())
+    }
+
+    /// 封面图片的文件名和尺寸，没有封面或无法识别封面的格式时返回 `None`
+    fn cover_image(&self) -> Option<CoverImage> {
+        let cover_data = self.metadata.cover.as_ref()?;
+        let reader = ImageReader::new(Cursor::new(cover_data))
+            .with_guessed_format()
+            .ok()?;
+        let format = reader.format()?;
+        // 无法解码的格式(例如webp)只能使用第一页的尺寸
+        let size = match reader.into_dimensions() {
+            Ok((width, height)) => PageSize { width, height },
+            Err(_) => self.pages.first()?.size,
+        };
+        Some(CoverImage {
+            file_name: format!("cover.{}", format.extensions_str()[0]),
+            media_type: format.to_mime_type().to_string(),
+            size,
+        })
+    }
+
+    fn content_opf(&self, cover: Option<&CoverImage>) -> String {
+        let metadata = self.opf_metadata(cover.is_some());
+
+        let mut manifest = String::new();
+        let _ = writeln!(
+            manifest,
+            r#"    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>"#
+        );
+        let mut spine = String::new();
+        if let Some(cover) = cover {
+            let _ = writeln!(
+                manifest,
+                r#"    <item id="cover-image" href="images/{}" media-type="{}" properties="cover-image"/>"#,
+                cover.file_name, cover.media_type
+            );
+            let _ = writeln!(
+                manifest,
+                r#"    <item id="cover" href="pages/cover.xhtml" media-type="application/xhtml+xml"/>"#
+            );
+            let _ = writeln!(spine, r#"    <itemref idref="cover"/>"#);
+        }
+        for page in &self.pages {
+            let number = page.number;
+            let _ = writeln!(
+                manifest,
+                r#"    <item id="img-{number:04}" href="images/{}" media-type="{}"/>"#,
+                page.img_name(),
+                page.media_type
+            );
+            let _ = writeln!(
+                manifest,
+                r#"    <item id="page-{number:04}" href="pages/{}" media-type="application/xhtml+xml"/>"#,
+                page.xhtml_name()
+            );
+            let _ = writeln!(spine, r#"    <itemref idref="page-{number:04}"/>"#);
+        }
+
+        let page_progression_direction = if self.metadata.is_right_to_left {
+            "rtl"
+        } else {
+            "ltr"
+        };
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id" prefix="rendition: http://www.idpf.org/vocab/rendition/#">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+{metadata}  </metadata>
+  <manifest>
+{manifest}  </manifest>
+  <spine page-progression-direction="{page_progression_direction}">
+{spine}  </spine>
+</package>
+"#
+        )
+    }
+
+    fn opf_metadata(&self, has_cover: bool) -> String {
+        let comic_info = &self.metadata.comic_info;
+        let title = if comic_info.title.is_empty() {
+            comic_info.series.clone()
+        } else {
+            format!("{} - {}", comic_info.series, comic_info.title)
+        };
+        // 年份为0表示日期未知，例如转换没有 `ComicInfo.xml` 的文件时
+        let date = (comic_info.year != 0).then(|| {
+            format!(
+                "{:04}-{:02}-{:02}",
+                comic_info.year, comic_info.month, comic_info.day
+            )
+        });
+        let language = if comic_info.language_iso.is_empty() {
+            "zh"
+        } else {
+            &comic_info.language_iso
+        };
+
+        let mut metadata = String::new();
+        let _ = writeln!(
+            metadata,
+            r#"    <dc:identifier id="book-id">{}</dc:identifier>"#,
+            escape_xml(&self.metadata.identifier)
+        );
+        let _ = writeln!(metadata, "    <dc:title>{}</dc:title>", escape_xml(&title));
+        let _ = writeln!(
+            metadata,
+            "    <dc:language>{}</dc:language>",
+            escape_xml(language)
+        );
+        for writer in split_list(&comic_info.writer) {
+            let _ = writeln!(
+                metadata,
+                "    <dc:creator>{}</dc:creator>",
+                escape_xml(writer)
+            );
+        }
+        let _ = writeln!(
+            metadata,
+            "    <dc:publisher>{}</dc:publisher>",
+            escape_xml(&comic_info.publisher)
+        );
+        for genre in split_list(&comic_info.genre) {
+            let _ = writeln!(
+                metadata,
+                "    <dc:subject>{}</dc:subject>",
+                escape_xml(genre)
+            );
+        }
+        let _ = writeln!(
+            metadata,
+            "    <dc:description>{}</dc:description>",
+            escape_xml(&comic_info.summary)
+        );
+        if let Some(date) = date {
+            let _ = writeln!(metadata, "    <dc:date>{date}</dc:date>");
+        }
+        // 与压缩包中条目的修改时间一样使用固定的时间，保证相同的内容总是生成完全相同的文件
+        let _ = writeln!(
+            metadata,
+            r#"    <meta property="dcterms:modified">1980-01-01T00:00:00Z</meta>"#
+        );
+        let _ = writeln!(
+            metadata,
+            r#"    <meta property="belongs-to-collection" id="series">{}</meta>"#,
+            escape_xml(&comic_info.series)
+        );
+        let _ = writeln!(
+            metadata,
+            r##"    <meta refines="#series" property="collection-type">series</meta>"##
+        );
+        if !comic_info.number.is_empty() {
+            let _ = writeln!(
+                metadata,
+                r##"    <meta refines="#series" property="group-position">{}</meta>"##,
+                escape_xml(&comic_info.number)
+            );
+        }
+        let _ = writeln!(
+            metadata,
+            r#"    <meta property="rendition:layout">pre-paginated</meta>"#
+        );
+        let _ = writeln!(
+            metadata,
+            r#"    <meta property="rendition:orientation">auto</meta>"#
+        );
+        let _ = writeln!(
+            metadata,
+            r#"    <meta property="rendition:spread">landscape</meta>"#
+        );
+        if has_cover {
+            // 兼容只认EPUB 2封面的阅读器
+            let _ = writeln!(
+                metadata,
+                r#"    <meta name="cover" content="cover-image"/>"#
+            );
+        }
+        metadata
+    }
+
+    /// 目录只列出章节，页面列表列出所有页
+    fn nav_xhtml(&self) -> String {
+        let mut toc = String::new();
+        for (title, number) in &self.chapters {
+            let _ = writeln!(
+                toc,
+                r#"        <li><a href="pages/p{number:04}.xhtml">{}</a></li>"#,
+                escape_xml(title)
+            );
+        }
+        let mut page_list = String::new();
+        for page in &self.pages {
+            let _ = writeln!(
+                page_list,
+                r#"        <li><a href="pages/{}">{}</a></li>"#,
+                page.xhtml_name(),
+                page.number
+            );
+        }
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+  <head>
+    <title>目录</title>
+  </head>
+  <body>
+    <nav epub:type="toc" id="toc">
+      <h1>目录</h1>
+      <ol>
+{toc}      </ol>
+    </nav>
+    <nav epub:type="page-list" hidden="">
+      <h1>页面列表</h1>
+      <ol>
+{page_list}      </ol>
+    </nav>
+  </body>
+</html>
+"#
+        )
+    }
+}
+
+impl EpubPage {
+    fn img_name(&self) -> String {
+        format!("p{:04}.{}", self.number, self.extension)
+    }
+
+    fn xhtml_name(&self) -> String {
+        format!("p{:04}.xhtml", self.number)
+    }
+}
+
+struct CoverImage {
+    file_name: String,
+    media_type: String,
+    size: PageSize,
+}
+
+/// 固定版式的页面，视口与图片尺寸一致，图片铺满整个页面
+fn page_xhtml(title: &str, img_name: &str, size: PageSize) -> String {
+    let PageSize { width, height } = size;
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+  <head>
+    <title>{}</title>
+    <meta name="viewport" content="width={width}, height={height}"/>
+    <style>html, body {{ margin: 0; padding: 0; }} img {{ display: block; width: {width}px; height: {height}px; }}</style>
+  </head>
+  <body>
+    <img src="../images/{img_name}" alt="{}"/>
+  </body>
+</html>
+"#,
+        escape_xml(title),
+        escape_xml(title)
+    )
+}
+
+/// `ComicInfo` 中的作者和类型都是用 `, ` 拼接的
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',').map(str::trim).filter(|s| !s.is_empty())
+}
@@ -6,9 +6,13 @@ use tauri_specta::Event;
 
 pub mod prelude {
     pub use crate::events::{
-        DownloadEndEvent, DownloadImageErrorEvent, DownloadImageSuccessEvent, DownloadPendingEvent,
-        DownloadSpeedEvent, DownloadStartEvent, RemoveWatermarkEndEvent, RemoveWatermarkErrorEvent,
-        RemoveWatermarkStartEvent, RemoveWatermarkSuccessEvent, SetProxyErrorEvent,
+        ConvertLibraryEndEvent, ConvertLibraryErrorEvent, ConvertLibraryStartEvent,
+        ConvertLibrarySuccessEvent, DownloadCancelEvent, DownloadEndEvent, DownloadImageErrorEvent,
+        DownloadImageSuccessEvent, DownloadPauseEvent, DownloadPendingEvent, DownloadResumeEvent,
+        DownloadSpeedEvent, DownloadStartEvent, RecordDownloadHistoryErrorEvent,
+        RemoveWatermarkEndEvent, RemoveWatermarkErrorEvent, RemoveWatermarkStartEvent,
+        RemoveWatermarkSuccessEvent, RestoreDownloadTasksErrorEvent, SaveSeriesMetadataErrorEvent,
+        SetProxyErrorEvent, SubscriptionCheckEndEvent, VerifyLibraryProgressEvent,
     };
 }
 
@@ -49,6 +53,53 @@ pub struct RemoveWatermarkEndEventPayload {
 #[derive(Serialize, Deserialize, Clone, Type, Event)]
 pub struct RemoveWatermarkEndEvent(pub RemoveWatermarkEndEventPayload);
 
+#[derive(Serialize, Deserialize, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct ConvertLibraryStartEventPayload {
+    pub total: u32,
+}
+#[derive(Serialize, Deserialize, Clone, Type, Event)]
+pub struct ConvertLibraryStartEvent(pub ConvertLibraryStartEventPayload);
+
+#[derive(Serialize, Deserialize, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct ConvertLibrarySuccessEventPayload {
+    pub src_path: PathBuf,
+    pub dest_path: PathBuf,
+    pub current: u32,
+}
+#[derive(Serialize, Deserialize, Clone, Type, Event)]
+pub struct ConvertLibrarySuccessEvent(pub ConvertLibrarySuccessEventPayload);
+
+#[derive(Serialize, Deserialize, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct ConvertLibraryErrorEventPayload {
+    pub src_path: PathBuf,
+    pub err_msg: String,
+}
+#[derive(Serialize, Deserialize, Clone, Type, Event)]
+pub struct ConvertLibraryErrorEvent(pub ConvertLibraryErrorEventPayload);
+
+#[derive(Serialize, Deserialize, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct ConvertLibraryEndEventPayload {
+    pub converted: u32,
+    pub failed: u32,
+}
+#[derive(Serialize, Deserialize, Clone, Type, Event)]
+pub struct ConvertLibraryEndEvent(pub ConvertLibraryEndEventPayload);
+
+#[derive(Serialize, Deserialize, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyLibraryProgressEventPayload {
+    pub comic_title: String,
+    pub episode_title: String,
+    pub current: u32,
+    pub total: u32,
+}
+#[derive(Serialize, Deserialize, Clone, Type, Event)]
+pub struct VerifyLibraryProgressEvent(pub VerifyLibraryProgressEventPayload);
+
 #[derive(Serialize, Deserialize, Clone, Type)]
 #[serde(rename_all = "camelCase")]
 pub struct DownloadPendingEventPayload {
@@ -64,6 +115,8 @@ pub struct DownloadPendingEvent(pub DownloadPendingEventPayload);
 pub struct DownloadStartEventPayload {
     pub id: i64,
     pub total: u32,
+    /// 开始下载前临时目录中已存在的有效图片数量
+    pub downloaded: u32,
 }
 #[derive(Serialize, Deserialize, Clone, Type, Event)]
 pub struct DownloadStartEvent(pub DownloadStartEventPayload);
@@ -93,18 +146,93 @@ pub struct DownloadImageErrorEvent(pub DownloadImageErrorEventPayload);
 pub struct DownloadEndEventPayload {
     pub id: i64,
     pub err_msg: Option<String>,
+    /// 重试次数用完后仍然下载失败的页码，从1开始
+    pub failed_pages: Vec<u32>,
 }
 #[derive(Serialize, Deserialize, Clone, Type, Event)]
 pub struct DownloadEndEvent(pub DownloadEndEventPayload);
 
+#[derive(Serialize, Deserialize, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadPauseEventPayload {
+    pub id: i64,
+}
+#[derive(Serialize, Deserialize, Clone, Type, Event)]
+pub struct DownloadPauseEvent(pub DownloadPauseEventPayload);
+
+#[derive(Serialize, Deserialize, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadResumeEventPayload {
+    pub id: i64,
+}
+#[derive(Serialize, Deserialize, Clone, Type, Event)]
+pub struct DownloadResumeEvent(pub DownloadResumeEventPayload);
+
+#[derive(Serialize, Deserialize, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadCancelEventPayload {
+    pub id: i64,
+}
+#[derive(Serialize, Deserialize, Clone, Type, Event)]
+pub struct DownloadCancelEvent(pub DownloadCancelEventPayload);
+
 #[derive(Serialize, Deserialize, Clone, Type)]
 #[serde(rename_all = "camelCase")]
 pub struct DownloadSpeedEventPayload {
-    pub speed: String,
+    /// 所有章节的下载速度，单位为字节/秒
+    pub bytes_per_sec: u64,
+    /// 本次启动以来下载的总字节数
+    pub total_bytes: u64,
+    /// 所有下载中的章节预计还需要下载的字节数
+    pub remaining_bytes: u64,
+    /// 所有下载中的章节预计还需要的秒数，无法估计时为 `None`
+    pub eta_secs: Option<u64>,
+    pub episodes: Vec<EpisodeDownloadSpeed>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct EpisodeDownloadSpeed {
+    pub id: i64,
+    /// 该章节的下载速度，单位为字节/秒
+    pub bytes_per_sec: u64,
+    /// 该章节本次下载的字节数
+    pub downloaded_bytes: u64,
+    /// 该章节还需要下载的图片数量
+    pub remaining_pages: u32,
+    /// 根据已下载图片的平均大小估计的剩余字节数
+    pub remaining_bytes: u64,
+    /// 该章节预计还需要的秒数，无法估计时为 `None`
+    pub eta_secs: Option<u64>,
 }
 #[derive(Serialize, Deserialize, Clone, Type, Event)]
 pub struct DownloadSpeedEvent(pub DownloadSpeedEventPayload);
 
+#[derive(Serialize, Deserialize, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordDownloadHistoryErrorEventPayload {
+    pub err_msg: String,
+}
+#[derive(Serialize, Deserialize, Clone, Type, Event)]
+pub struct RecordDownloadHistoryErrorEvent(pub RecordDownloadHistoryErrorEventPayload);
+
+#[derive(Serialize, Deserialize, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreDownloadTasksErrorEventPayload {
+    pub err_msg: String,
+}
+#[derive(Serialize, Deserialize, Clone, Type, Event)]
+pub struct RestoreDownloadTasksErrorEvent(pub RestoreDownloadTasksErrorEventPayload);
+
+#[derive(Serialize, Deserialize, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveSeriesMetadataErrorEventPayload {
+    pub comic_title: String,
+    pub err_msg: String,
+}
+#[derive(Serialize, Deserialize, Clone, Type, Event)]
+pub struct SaveSeriesMetadataErrorEvent(pub SaveSeriesMetadataErrorEventPayload);
+
 #[derive(Serialize, Deserialize, Clone, Type)]
 #[serde(rename_all = "camelCase")]
 pub struct SetProxyErrorEventPayload {
@@ -112,3 +240,21 @@ pub struct SetProxyErrorEventPayload {
 }
 #[derive(Serialize, Deserialize, Clone, Type, Event)]
 pub struct SetProxyErrorEvent(pub SetProxyErrorEventPayload);
+
+#[derive(Serialize, Deserialize, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriptionCheckEndEventPayload {
+    pub comics: Vec<SubscriptionCheckResult>,
+}
+#[derive(Serialize, Deserialize, Clone, Type, Event)]
+pub struct SubscriptionCheckEndEvent(pub SubscriptionCheckEndEventPayload);
+
+#[derive(Serialize, Deserialize, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriptionCheckResult {
+    pub comic_id: i64,
+    pub comic_title: String,
+    /// 本次检查加入下载队列的章节标题
+    pub queued_episode_titles: Vec<String>,
+    pub err_msg: Option<String>,
+}
@@ -1,18 +1,34 @@
+mod archive;
 mod bili_client;
 mod commands;
 mod config;
+mod convert;
+mod download_history;
 mod download_manager;
+mod epub;
 mod errors;
 mod events;
 mod extensions;
+mod library;
+mod opf;
+mod pdf;
 mod responses;
+mod series_metadata;
+mod subscription_manager;
+mod token_bucket;
 mod types;
 mod utils;
+mod verify;
+mod volume;
+mod watermark;
 
 use crate::commands::*;
 use crate::config::Config;
+use crate::download_history::DownloadHistory;
 use crate::download_manager::DownloadManager;
 use crate::events::prelude::*;
+use crate::library::Library;
+use crate::subscription_manager::SubscriptionManager;
 use anyhow::Context;
 use parking_lot::RwLock;
 use tauri::{Manager, Wry};
@@ -22,6 +38,7 @@ fn generate_context() -> tauri::Context<Wry> {
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
+#[allow(clippy::too_many_lines)]
 pub fn run() {
     let builder = tauri_specta::Builder::<Wry>::new()
         .commands(tauri_specta::collect_commands![
@@ -33,8 +50,25 @@ pub fn run() {
             search,
             get_comic,
             download_episodes,
+            download_comic,
+            get_download_tasks,
+            pause_download,
+            resume_download,
+            cancel_download,
             show_path_in_file_manager,
             get_user_profile,
+            subscribe,
+            unsubscribe,
+            list_subscriptions,
+            get_download_history,
+            clear_download_history,
+            remove_watermark,
+            merge_episodes,
+            export_opf,
+            convert_library,
+            scan_library,
+            get_library,
+            verify_library,
             check_update,
         ])
         .events(tauri_specta::collect_events![
@@ -42,13 +76,25 @@ pub fn run() {
             RemoveWatermarkSuccessEvent,
             RemoveWatermarkErrorEvent,
             RemoveWatermarkEndEvent,
+            ConvertLibraryStartEvent,
+            ConvertLibrarySuccessEvent,
+            ConvertLibraryErrorEvent,
+            ConvertLibraryEndEvent,
+            VerifyLibraryProgressEvent,
             DownloadPendingEvent,
             DownloadStartEvent,
             DownloadImageSuccessEvent,
             DownloadImageErrorEvent,
             DownloadEndEvent,
+            DownloadPauseEvent,
+            DownloadResumeEvent,
+            DownloadCancelEvent,
             DownloadSpeedEvent,
+            RecordDownloadHistoryErrorEvent,
+            RestoreDownloadTasksErrorEvent,
             SetProxyErrorEvent,
+            SaveSeriesMetadataErrorEvent,
+            SubscriptionCheckEndEvent,
         ]);
 
     #[cfg(debug_assertions)]
@@ -81,12 +127,27 @@ pub fn run() {
             let config = RwLock::new(Config::new(app.handle())?);
             app.manage(config);
 
+            let download_history = DownloadHistory::new(app.handle());
+            app.manage(download_history);
+
+            let library = Library::new(app.handle());
+            app.manage(library);
+
             let download_manager = DownloadManager::new(app.handle());
             app.manage(download_manager);
 
             let bili_client = bili_client::BiliClient::new(app.handle().clone());
             app.manage(bili_client);
 
+            let subscription_manager = SubscriptionManager::new(app.handle())?;
+            app.manage(subscription_manager);
+
+            // 恢复上次退出时未完成的下载任务
+            let download_manager = app.state::<DownloadManager>().inner().clone();
+            tauri::async_runtime::spawn(async move {
+                download_manager.restore_tasks().await;
+            });
+
             Ok(())
         })
         .run(generate_context())
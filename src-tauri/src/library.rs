@@ -0,0 +1,415 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context};
+use parking_lot::RwLock;
+use tauri::{AppHandle, Manager};
+
+use crate::archive;
+use crate::config::Config;
+use crate::download_manager::DOWNLOADING_DIR_PREFIX;
+use crate::extensions::AnyhowErrorToStringChain;
+use crate::types::{ArchiveFormat, LibraryComic, LibraryEpisode, LibraryScope};
+use crate::utils;
+use crate::utils::natural_cmp;
+
+/// 可以识别的章节和单行本文件格式，文件夹格式的章节是目录，不在其中
+const FILE_ARCHIVE_FORMATS: [ArchiveFormat; 4] = [
+    ArchiveFormat::Zip,
+    ArchiveFormat::Cbz,
+    ArchiveFormat::Pdf,
+    ArchiveFormat::Epub,
+];
+
+/// 校验时发现无法正常阅读的章节会被加上这个前缀，之后不再被视为已下载，重新下载成功后会被删除
+pub const CORRUPTED_PREFIX: &str = ".损坏-";
+
+/// 本地漫画库，以漫画目录名为键索引 `download_dir` 中已下载的漫画
+///
+/// 漫画通过 `scan` 扫描后才会出现在索引中，之后下载、转换或合并的章节会增量地更新到索引中
+pub struct Library {
+    app: AppHandle,
+    comics: RwLock<BTreeMap<String, LibraryComic>>,
+}
+
+impl Library {
+    pub fn new(app: &AppHandle) -> Self {
+        Self {
+            app: app.clone(),
+            comics: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// 重新扫描 `scope` 范围内的漫画并更新索引，返回扫描到的漫画
+    pub fn scan(&self, scope: &LibraryScope) -> anyhow::Result<Vec<LibraryComic>> {
+        let download_dir = self.download_dir();
+        let comic_dirs = match get_comic_dirs(&download_dir, scope) {
+            Ok(comic_dirs) => comic_dirs,
+            Err(err) => {
+                // 漫画目录已经不存在了，索引中的记录也没有用了
+                if let LibraryScope::Comic(comic_title) = scope {
+                    self.comics.write().remove(comic_title);
+                }
+                return Err(err);
+            }
+        };
+        let mut comics = Vec::with_capacity(comic_dirs.len());
+        for comic_dir in comic_dirs {
+            comics.push(scan_comic(&comic_dir)?);
+        }
+
+        let mut index = self.comics.write();
+        if *scope == LibraryScope::All {
+            index.clear();
+        }
+        for comic in &comics {
+            index.insert(comic.comic_title.clone(), comic.clone());
+        }
+        Ok(comics)
+    }
+
+    /// 获取索引中的所有漫画，只包含已经扫描过的漫画
+    ///
+    /// 扫描后被其他程序删除或移动的漫画和章节会被跳过
+    pub fn get_comics(&self) -> Vec<LibraryComic> {
+        self.comics
+            .read()
+            .values()
+            .filter(|comic| comic.comic_dir.is_dir())
+            .map(|comic| {
+                let mut comic = comic.clone();
+                comic.episodes.retain(|ep| ep.path.exists());
+                comic.bytes = comic.episodes.iter().map(|ep| ep.bytes).sum();
+                comic
+            })
+            .collect()
+    }
+
+    /// 漫画 `comic_title` 中是否有名为 `episode_title` 且完整的章节，格式不限
+    ///
+    /// 优先查询索引，索引中的章节已被其他程序删除时不算已下载，
+    /// 只有漫画还没有被索引时(例如扫描失败)才逐个格式检查磁盘上是否存在该章节
+    pub fn is_downloaded(&self, comic_title: &str, episode_title: &str) -> bool {
+        if let Some(comic) = self.comics.read().get(comic_title) {
+            return comic
+                .episodes
+                .iter()
+                .any(|ep| ep.name == episode_title && !ep.is_incomplete && ep.path.exists());
+        }
+        let comic_dir = self.download_dir().join(comic_title);
+        if !comic_dir.is_dir() {
+            return false;
+        }
+        std::iter::once(ArchiveFormat::Image)
+            .chain(FILE_ARCHIVE_FORMATS)
+            .any(|archive_format| {
+                let path = archive::get_archive_path(&comic_dir, episode_title, &archive_format);
+                if archive_format == ArchiveFormat::Image {
+                    path.is_dir()
+                } else {
+                    path.is_file()
+                }
+            })
+    }
+
+    /// 漫画 `comic_title` 还没有被索引时扫描它，漫画目录不存在时什么也不做
+    ///
+    /// 扫描需要读取所有章节，应在阻塞线程中调用
+    pub fn ensure_scanned(&self, comic_title: &str) -> anyhow::Result<()> {
+        if self.comics.read().contains_key(comic_title) {
+            return Ok(());
+        }
+        if !self.download_dir().join(comic_title).is_dir() {
+            return Ok(());
+        }
+        self.scan(&LibraryScope::Comic(comic_title.to_string()))?;
+        Ok(())
+    }
+
+    /// 清空索引，下载目录被修改后调用，之后需要重新扫描
+    pub fn clear(&self) {
+        self.comics.write().clear();
+    }
+
+    /// 将刚保存的 `archive_path` 更新到索引中，同名的临时下载目录会从索引中移除
+    ///
+    /// 所在的漫画还没有被扫描过时什么也不做，等到扫描时再完整地扫描
+    pub fn index_archive(&self, archive_path: &Path, archive_format: &ArchiveFormat) {
+        let Some(comic_title) = get_comic_title(archive_path) else {
+            return;
+        };
+        let mut index = self.comics.write();
+        let Some(comic) = index.get_mut(&comic_title) else {
+            return;
+        };
+        let episode = scan_archive(archive_path, archive_format);
+        comic.episodes.retain(|ep| {
+            let is_temp_dir = ep.name == episode.name
+                && ep
+                    .path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with(DOWNLOADING_DIR_PREFIX));
+            ep.path != archive_path && !is_temp_dir
+        });
+        comic.episodes.push(episode);
+        sort_episodes(&mut comic.episodes);
+        comic.bytes = comic.episodes.iter().map(|ep| ep.bytes).sum();
+    }
+
+    /// 将已被删除的 `archive_path` 从索引中移除
+    pub fn remove_archive(&self, archive_path: &Path) {
+        let Some(comic_title) = get_comic_title(archive_path) else {
+            return;
+        };
+        if let Some(comic) = self.comics.write().get_mut(&comic_title) {
+            comic.episodes.retain(|ep| ep.path != archive_path);
+            comic.bytes = comic.episodes.iter().map(|ep| ep.bytes).sum();
+        }
+    }
+
+    /// 给校验失败的 `archive_path` 加上 `CORRUPTED_PREFIX` 并在索引中标记为不完整，返回重命名后的路径
+    ///
+    /// 重命名后它不再被视为已下载，重启或重新扫描后依然会被标记为不完整
+    pub fn mark_corrupted(&self, archive_path: &Path, err_msg: String) -> anyhow::Result<PathBuf> {
+        let file_name = archive_path
+            .file_name()
+            .context(format!("{archive_path:?} 没有文件名"))?
+            .to_string_lossy();
+        let corrupted_path = archive_path.with_file_name(format!("{CORRUPTED_PREFIX}{file_name}"));
+        // 之前校验失败且还没重新下载的同名章节已经没有用了
+        remove_archive_file(&corrupted_path)?;
+        std::fs::rename(archive_path, &corrupted_path).context(format!(
+            "将 {archive_path:?} 重命名为 {corrupted_path:?} 失败"
+        ))?;
+
+        let Some(comic_title) = get_comic_title(archive_path) else {
+            return Ok(corrupted_path);
+        };
+        let mut index = self.comics.write();
+        let Some(comic) = index.get_mut(&comic_title) else {
+            return Ok(corrupted_path);
+        };
+        comic.episodes.retain(|ep| ep.path != corrupted_path);
+        if let Some(ep) = comic.episodes.iter_mut().find(|ep| ep.path == archive_path) {
+            ep.path.clone_from(&corrupted_path);
+            ep.is_incomplete = true;
+            ep.err_msg = Some(err_msg);
+        }
+        comic.bytes = comic.episodes.iter().map(|ep| ep.bytes).sum();
+        Ok(corrupted_path)
+    }
+
+    /// 删除与刚保存的 `archive_path` 同名的损坏章节，不限格式
+    pub fn remove_corrupted(&self, archive_path: &Path, archive_format: &ArchiveFormat) {
+        let Some(comic_dir) = archive_path.parent() else {
+            return;
+        };
+        let name = get_archive_name(archive_path, archive_format);
+        let corrupted_name = format!("{CORRUPTED_PREFIX}{name}");
+        for archive_format in std::iter::once(ArchiveFormat::Image).chain(FILE_ARCHIVE_FORMATS) {
+            let corrupted_path =
+                archive::get_archive_path(comic_dir, &corrupted_name, &archive_format);
+            // 删除失败也不影响新下载的章节，下次扫描时它依然会被标记为不完整
+            if remove_archive_file(&corrupted_path).is_ok() {
+                self.remove_archive(&corrupted_path);
+            }
+        }
+    }
+
+    fn download_dir(&self) -> PathBuf {
+        self.app
+            .state::<RwLock<Config>>()
+            .read()
+            .download_dir
+            .clone()
+    }
+}
+
+/// 扫描 `comic_dir` 中所有格式的章节和单行本，以及下载中或下载失败残留的临时下载目录
+fn scan_comic(comic_dir: &Path) -> anyhow::Result<LibraryComic> {
+    let mut episodes = Vec::new();
+    for entry in std::fs::read_dir(comic_dir)
+        .context(format!("读取目录 {comic_dir:?} 失败"))?
+        .filter_map(Result::ok)
+    {
+        let path = entry.path();
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if let Some(name) = file_name.strip_prefix(DOWNLOADING_DIR_PREFIX) {
+            if path.is_dir() {
+                let page_count =
+                    archive::get_sorted_page_paths(&path).map_or(0, |paths| paths.len());
+                episodes.push(LibraryEpisode {
+                    name: name.to_string(),
+                    page_count: u32::try_from(page_count).unwrap_or(u32::MAX),
+                    bytes: utils::path_size(&path),
+                    path,
+                    is_incomplete: true,
+                    ..Default::default()
+                });
+            }
+            continue;
+        }
+        if let Some(name) = file_name.strip_prefix(CORRUPTED_PREFIX) {
+            if let Some(archive_format) = get_archive_format(&path) {
+                episodes.push(LibraryEpisode {
+                    name: get_archive_name(Path::new(name), &archive_format),
+                    is_incomplete: true,
+                    err_msg: Some("校验时发现无法正常阅读，重新下载后会被删除".to_string()),
+                    ..scan_archive(&path, &archive_format)
+                });
+            }
+            continue;
+        }
+        if is_hidden(&path) {
+            continue;
+        }
+        if let Some(archive_format) = get_archive_format(&path) {
+            episodes.push(scan_archive(&path, &archive_format));
+        }
+    }
+    sort_episodes(&mut episodes);
+
+    Ok(LibraryComic {
+        comic_title: comic_dir
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        comic_dir: comic_dir.to_path_buf(),
+        bytes: episodes.iter().map(|ep| ep.bytes).sum(),
+        episodes,
+    })
+}
+
+/// 读取 `archive_path` 的图片数量和 `ComicInfo.xml`，读取失败时标记为不完整
+fn scan_archive(archive_path: &Path, archive_format: &ArchiveFormat) -> LibraryEpisode {
+    let mut episode = LibraryEpisode {
+        name: get_archive_name(archive_path, archive_format),
+        path: archive_path.to_path_buf(),
+        archive_format: archive_format.clone(),
+        bytes: utils::path_size(archive_path),
+        ..Default::default()
+    };
+    let result = archive::count_pages(archive_path, archive_format).and_then(|page_count| {
+        let comic_info = archive::read_comic_info(archive_path, archive_format)?;
+        Ok((page_count, comic_info))
+    });
+    match result {
+        Ok((page_count, comic_info)) => {
+            let page_count = u32::try_from(page_count).unwrap_or(u32::MAX);
+            let expected_page_count = comic_info.as_ref().map_or(0, |info| info.page_count);
+            episode.page_count = page_count;
+            episode.is_incomplete = page_count == 0 || i64::from(page_count) < expected_page_count;
+            episode.comic_info = comic_info;
+        }
+        Err(err) => {
+            let err = err.context(format!("读取 {archive_path:?} 失败"));
+            episode.is_incomplete = true;
+            episode.err_msg = Some(err.to_string_chain());
+        }
+    }
+    episode
+}
+
+/// 根据路径判断章节或单行本的格式，目录是文件夹格式，无法识别的文件返回 `None`
+fn get_archive_format(path: &Path) -> Option<ArchiveFormat> {
+    if path.is_dir() {
+        return Some(ArchiveFormat::Image);
+    }
+    let ext = path.extension()?.to_string_lossy().to_string();
+    FILE_ARCHIVE_FORMATS
+        .into_iter()
+        .find(|archive_format| ext.eq_ignore_ascii_case(archive_format.extension()))
+}
+
+/// `archive_path` 所在的漫画目录名
+fn get_comic_title(archive_path: &Path) -> Option<String> {
+    let comic_dir = archive_path.parent()?;
+    Some(comic_dir.file_name()?.to_string_lossy().to_string())
+}
+
+fn sort_episodes(episodes: &mut [LibraryEpisode]) {
+    episodes.sort_by(|a, b| natural_cmp(&a.name, &b.name));
+}
+
+/// 获取 `download_dir` 中 `scope` 范围内的漫画目录，按目录名自然排序
+///
+/// 以 `.` 开头的是临时目录，会被跳过
+pub fn get_comic_dirs(download_dir: &Path, scope: &LibraryScope) -> anyhow::Result<Vec<PathBuf>> {
+    if let LibraryScope::Comic(comic_title) = scope {
+        let comic_dir = download_dir.join(comic_title);
+        if !comic_dir.is_dir() {
+            return Err(anyhow!("漫画目录 {comic_dir:?} 不存在"));
+        }
+        return Ok(vec![comic_dir]);
+    }
+    if !download_dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut comic_dirs: Vec<PathBuf> = std::fs::read_dir(download_dir)
+        .context(format!("读取目录 {download_dir:?} 失败"))?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir() && !is_hidden(path))
+        .collect();
+    sort_by_file_name(&mut comic_dirs);
+    Ok(comic_dirs)
+}
+
+/// 获取 `comic_dir` 中以 `archive_format` 格式保存的章节和单行本，按文件名自然排序
+pub fn get_archive_paths(
+    comic_dir: &Path,
+    archive_format: &ArchiveFormat,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let mut archive_paths: Vec<PathBuf> = std::fs::read_dir(comic_dir)
+        .context(format!("读取目录 {comic_dir:?} 失败"))?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| !is_hidden(path))
+        .filter(|path| match archive_format {
+            ArchiveFormat::Image => path.is_dir(),
+            _ => {
+                path.is_file()
+                    && path.extension().is_some_and(|ext| {
+                        ext.to_string_lossy()
+                            .eq_ignore_ascii_case(archive_format.extension())
+                    })
+            }
+        })
+        .collect();
+    sort_by_file_name(&mut archive_paths);
+    Ok(archive_paths)
+}
+
+/// 章节或单行本的名称，即去掉扩展名后的文件名
+pub fn get_archive_name(archive_path: &Path, archive_format: &ArchiveFormat) -> String {
+    let name = if *archive_format == ArchiveFormat::Image {
+        archive_path.file_name()
+    } else {
+        archive_path.file_stem()
+    };
+    name.map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// 删除 `path` 处的章节或单行本，文件夹格式的章节是目录，不存在时什么也不做
+fn remove_archive_file(path: &Path) -> anyhow::Result<()> {
+    if path.is_dir() {
+        std::fs::remove_dir_all(path).context(format!("删除 {path:?} 失败"))?;
+    } else if path.exists() {
+        std::fs::remove_file(path).context(format!("删除 {path:?} 失败"))?;
+    }
+    Ok(())
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+fn sort_by_file_name(paths: &mut [PathBuf]) {
+    paths.sort_by(|a, b| {
+        let a = a.file_name().unwrap_or_default().to_string_lossy();
+        let b = b.file_name().unwrap_or_default().to_string_lossy();
+        natural_cmp(&a, &b)
+    });
+}
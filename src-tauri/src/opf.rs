@@ -0,0 +1,163 @@
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context};
+use parking_lot::RwLock;
+use tauri::{AppHandle, Manager};
+
+use crate::archive;
+use crate::config::Config;
+use crate::types::{Comic, ComicInfo};
+use crate::utils::{escape_xml, filename_filter};
+use crate::volume;
+
+/// 导出Calibre能识别的OPF元数据，返回OPF文件的路径
+///
+/// `volume` 为 `None` 时导出整部漫画的 `metadata.opf` 到漫画目录，
+/// 否则导出第 `volume` 卷单行本的元数据到单行本旁边的同名 `.opf` 文件
+pub fn export_opf(app: &AppHandle, comic: &Comic, volume: Option<i64>) -> anyhow::Result<PathBuf> {
+    let download_dir = app.state::<RwLock<Config>>().read().download_dir.clone();
+    let comic_title = filename_filter(&comic.title);
+    let comic_dir = download_dir.join(&comic_title);
+    if !comic_dir.is_dir() {
+        return Err(anyhow!("漫画 {comic_title} 还没有下载过任何章节"));
+    }
+
+    let (opf_path, comic_info) = match volume {
+        None => (comic_dir.join("metadata.opf"), None),
+        Some(volume) => {
+            let volume_name = volume::get_volume_name(&comic_title, volume);
+            let Some((volume_path, archive_format)) = volume::find_volume(&comic_dir, &volume_name)
+            else {
+                return Err(anyhow!("第{volume}卷单行本 {volume_name} 不存在"));
+            };
+            // pdf和epub中没有 `ComicInfo.xml`，只能用第一个章节的 `ComicInfo` 代替，
+            // 但无法得知单行本包含哪些章节，所以不使用其中的日期
+            let comic_info = match archive::read_comic_info(&volume_path, &archive_format)? {
+                Some(comic_info) => comic_info,
+                None => ComicInfo {
+                    title: format!("第{volume}卷"),
+                    number: volume.to_string(),
+                    volume: Some(volume),
+                    year: 0,
+                    month: 0,
+                    day: 0,
+                    ..comic
+                        .episode_infos
+                        .first()
+                        .map(|ep| ep.comic_info.clone())
+                        .unwrap_or_default()
+                },
+            };
+            let opf_path = comic_dir.join(format!("{volume_name}.opf"));
+            (opf_path, Some(comic_info))
+        }
+    };
+    let has_cover = comic_dir.join("cover.jpg").is_file();
+    let opf = build_opf(comic, comic_info.as_ref(), has_cover);
+    archive::write_atomically(&opf_path, |temp_opf_path| {
+        std::fs::write(temp_opf_path, &opf).context(format!("写入 {temp_opf_path:?} 失败"))
+    })?;
+    Ok(opf_path)
+}
+
+/// 用 `comic` 和 `comic_info` 构建Calibre能识别的 OPF 2.0 元数据
+///
+/// 有 `comic_info` 时标题使用单行本或章节的标题，系列序号使用 `ComicInfo` 中的 `Number`，即章节的 `ord` 或卷号
+fn build_opf(comic: &Comic, comic_info: Option<&ComicInfo>, has_cover: bool) -> String {
+    let title = match comic_info {
+        Some(comic_info) => format!("{} {}", comic.title, comic_info.title),
+        None => comic.title.clone(),
+    };
+    // `release_time` 的格式为 `2020-01-01` 或 `2020-01-01 00:00:00`，`ComicInfo` 中的年份为0表示日期未知
+    let date = match comic_info {
+        Some(comic_info) if comic_info.year == 0 => None,
+        Some(comic_info) => Some(format!(
+            "{:04}-{:02}-{:02}",
+            comic_info.year, comic_info.month, comic_info.day
+        )),
+        None => comic.release_time.get(..10).map(ToString::to_string),
+    };
+    let language = comic_info
+        .map(|comic_info| comic_info.language_iso.as_str())
+        .filter(|language| !language.is_empty())
+        .unwrap_or("zh");
+    let description = if comic.introduction.is_empty() {
+        &comic.evaluate
+    } else {
+        &comic.introduction
+    };
+    let mut subjects: Vec<&str> = Vec::new();
+    for subject in comic
+        .styles
+        .iter()
+        .map(String::as_str)
+        .chain(comic.tags.iter().map(|tag| tag.name.as_str()))
+    {
+        if !subject.is_empty() && !subjects.contains(&subject) {
+            subjects.push(subject);
+        }
+    }
+    let series_index = comic_info.and_then(|comic_info| comic_info.number.parse::<f64>().ok());
+
+    let mut metadata = String::new();
+    let _ = writeln!(
+        metadata,
+        r#"    <dc:identifier opf:scheme="bilibili-manga" id="id">{}</dc:identifier>"#,
+        comic.id
+    );
+    let _ = writeln!(metadata, "    <dc:title>{}</dc:title>", escape_xml(&title));
+    for author in &comic.authors {
+        let _ = writeln!(
+            metadata,
+            r#"    <dc:creator opf:role="aut">{}</dc:creator>"#,
+            escape_xml(&author.name)
+        );
+    }
+    let _ = writeln!(metadata, "    <dc:publisher>哔哩哔哩漫画</dc:publisher>");
+    if let Some(date) = date {
+        let _ = writeln!(metadata, "    <dc:date>{date}T00:00:00+00:00</dc:date>");
+    }
+    let _ = writeln!(
+        metadata,
+        "    <dc:description>{}</dc:description>",
+        escape_xml(description)
+    );
+    let _ = writeln!(
+        metadata,
+        "    <dc:language>{}</dc:language>",
+        escape_xml(language)
+    );
+    for subject in subjects {
+        let _ = writeln!(
+            metadata,
+            "    <dc:subject>{}</dc:subject>",
+            escape_xml(subject)
+        );
+    }
+    let _ = writeln!(
+        metadata,
+        r#"    <meta name="calibre:series" content="{}"/>"#,
+        escape_xml(&comic.title)
+    );
+    if let Some(series_index) = series_index {
+        let _ = writeln!(
+            metadata,
+            r#"    <meta name="calibre:series_index" content="{series_index}"/>"#
+        );
+    }
+
+    let guide = if has_cover {
+        "  <guide>\n    <reference type=\"cover\" title=\"Cover\" href=\"cover.jpg\"/>\n  </guide>\n"
+    } else {
+        ""
+    };
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" unique-identifier="id" version="2.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:opf="http://www.idpf.org/2007/opf">
+{metadata}  </metadata>
+{guide}</package>
+"#
+    )
+}
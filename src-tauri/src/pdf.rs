@@ -0,0 +1,319 @@
+use std::fs::File;
+use std::io::{BufWriter, Cursor};
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+use image::codecs::jpeg::JpegEncoder;
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Document, Object, ObjectId, Stream, StringFormat};
+
+use crate::archive;
+use crate::archive::PageSize;
+use crate::types::ComicInfo;
+
+/// 需要重新编码为jpg时使用的质量
+const JPEG_QUALITY: u8 = 95;
+
+/// 将 `temp_download_dir` 中的图片保存为 `pdf_path`，保存成功后删除 `temp_download_dir`
+///
+/// 每张图片占一页，页面尺寸优先使用 `page_sizes` 中对应的尺寸，未知时使用图片本身的尺寸
+pub fn save_pdf(
+    temp_download_dir: &Path,
+    comic_info: &ComicInfo,
+    pdf_path: &Path,
+    page_sizes: &[PageSize],
+) -> anyhow::Result<()> {
+    let page_paths = archive::get_sorted_page_paths(temp_download_dir)?;
+    let mut pdf_builder = PdfBuilder::new();
+    for (i, page_path) in page_paths.iter().enumerate() {
+        let page_size = page_sizes.get(i).copied().unwrap_or_default();
+        pdf_builder.add_page(page_path, page_size)?;
+    }
+    pdf_builder.save(comic_info, pdf_path)?;
+
+    std::fs::remove_dir_all(temp_download_dir)
+        .context(format!("删除 {temp_download_dir:?} 失败"))?;
+    Ok(())
+}
+
+/// 逐页构建PDF，所有图片都以jpg的形式直接嵌入，不会重新压缩
+pub struct PdfBuilder {
+    doc: Document,
+    pages_id: ObjectId,
+    page_ids: Vec<ObjectId>,
+    outlines_id: Option<ObjectId>,
+}
+
+impl PdfBuilder {
+    pub fn new() -> Self {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        Self {
+            doc,
+            pages_id,
+            page_ids: Vec::new(),
+            outlines_id: None,
+        }
+    }
+
+    /// 添加一页，返回该页在PDF中的 `ObjectId`
+    pub fn add_page(&mut self, img_path: &Path, page_size: PageSize) -> anyhow::Result<ObjectId> {
+        let img = PdfImage::load(img_path).context(format!("读取图片 {img_path:?} 失败"))?;
+        let (page_width, page_height) = if page_size.width == 0 || page_size.height == 0 {
+            (img.width, img.height)
+        } else {
+            (page_size.width, page_size.height)
+        };
+
+        let img_stream = Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => img.width,
+                "Height" => img.height,
+                "ColorSpace" => img.color_space,
+                "BitsPerComponent" => 8,
+                "Filter" => "DCTDecode",
+            },
+            img.data,
+        )
+        .with_compression(false);
+        let img_id = self.doc.add_object(img_stream);
+        // 将图片拉伸到整个页面，1像素对应1pt
+        let content = Content {
+            operations: vec![
+                Operation::new("q", vec![]),
+                Operation::new(
+                    "cm",
+                    vec![
+                        page_width.into(),
+                        0.into(),
+                        0.into(),
+                        page_height.into(),
+                        0.into(),
+                        0.into(),
+                    ],
+                ),
+                Operation::new("Do", vec!["Im0".into()]),
+                Operation::new("Q", vec![]),
+            ],
+        };
+        let content_data = content.encode().context("编码PDF页面内容失败")?;
+        let content_id = self
+            .doc
+            .add_object(Stream::new(dictionary! {}, content_data));
+        let page_id = self.doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => self.pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), page_width.into(), page_height.into()],
+            "Contents" => content_id,
+            "Resources" => dictionary! {
+                "XObject" => dictionary! { "Im0" => img_id },
+            },
+        });
+        self.page_ids.push(page_id);
+        Ok(page_id)
+    }
+
+    /// 添加书签，每个书签跳转到对应的页
+    pub fn add_outlines(&mut self, outlines: &[(String, ObjectId)]) {
+        if outlines.is_empty() {
+            return;
+        }
+        let outlines_id = self.doc.new_object_id();
+        let item_ids: Vec<ObjectId> = outlines.iter().map(|_| self.doc.new_object_id()).collect();
+        for (i, ((title, page_id), item_id)) in outlines.iter().zip(&item_ids).enumerate() {
+            let mut item = dictionary! {
+                "Title" => text_string(title),
+                "Parent" => outlines_id,
+                "Dest" => vec![(*page_id).into(), "Fit".into()],
+            };
+            if i > 0 {
+                item.set("Prev", item_ids[i - 1]);
+            }
+            if let Some(next_id) = item_ids.get(i + 1) {
+                item.set("Next", *next_id);
+            }
+            self.doc.objects.insert(*item_id, Object::Dictionary(item));
+        }
+        let outlines_dict = dictionary! {
+            "Type" => "Outlines",
+            "First" => item_ids[0],
+            "Last" => item_ids[item_ids.len() - 1],
+            "Count" => i64::try_from(item_ids.len()).unwrap_or(i64::MAX),
+        };
+        self.doc
+            .objects
+            .insert(outlines_id, Object::Dictionary(outlines_dict));
+        self.outlines_id = Some(outlines_id);
+    }
+
+    /// 完成构建，通过 `archive::write_atomically` 写入 `pdf_path`
+    pub fn save(self, comic_info: &ComicInfo, pdf_path: &Path) -> anyhow::Result<()> {
+        let mut doc = self.build(comic_info);
+        archive::write_atomically(pdf_path, |temp_pdf_path| {
+            let file =
+                File::create(temp_pdf_path).context(format!("创建 {temp_pdf_path:?} 失败"))?;
+            doc.save_to(&mut BufWriter::new(file))
+                .context(format!("写入 {temp_pdf_path:?} 失败"))?;
+            Ok(())
+        })
+    }
+
+    /// 用 `comic_info` 填写文档信息
+    fn build(mut self, comic_info: &ComicInfo) -> Document {
+        let page_count = i64::try_from(self.page_ids.len()).unwrap_or(i64::MAX);
+        let kids: Vec<Object> = self.page_ids.iter().map(|id| (*id).into()).collect();
+        let pages = dictionary! {
+            "Type" => "Pages",
+            "Kids" => kids,
+            "Count" => page_count,
+        };
+        self.doc
+            .objects
+            .insert(self.pages_id, Object::Dictionary(pages));
+
+        let mut catalog = dictionary! {
+            "Type" => "Catalog",
+            "Pages" => self.pages_id,
+        };
+        if let Some(outlines_id) = self.outlines_id {
+            catalog.set("Outlines", outlines_id);
+            catalog.set("PageMode", "UseOutlines");
+        }
+        let catalog_id = self.doc.add_object(catalog);
+        self.doc.trailer.set("Root", catalog_id);
+
+        let title = if comic_info.title.is_empty() {
+            comic_info.series.clone()
+        } else {
+            format!("{} - {}", comic_info.series, comic_info.title)
+        };
+        let info_id = self.doc.add_object(dictionary! {
+            "Title" => text_string(&title),
+            "Author" => text_string(&comic_info.writer),
+            "Subject" => text_string(&comic_info.summary),
+            "Keywords" => text_string(&comic_info.genre),
+            "Creator" => text_string(&comic_info.publisher),
+        });
+        self.doc.trailer.set("Info", info_id);
+
+        self.doc
+    }
+}
+
+/// 按页的顺序将 `pdf_path` 中每页的图片提取到 `dest_dir`，图片依次命名为 `001.jpg`、`002.jpg`...
+///
+/// 只支持由 `PdfBuilder` 生成的pdf，即每页只有一张以jpg形式嵌入的图片
+pub fn extract_pages(pdf_path: &Path, dest_dir: &Path) -> anyhow::Result<()> {
+    let doc = Document::load(pdf_path).context(format!("读取 {pdf_path:?} 失败"))?;
+    for (page_number, page_id) in doc.get_pages() {
+        let jpg_data = get_page_jpg_data(&doc, page_id)
+            .context(format!("提取 {pdf_path:?} 第 {page_number} 页的图片失败"))?;
+        let page_path = dest_dir.join(format!("{page_number:03}.jpg"));
+        std::fs::write(&page_path, jpg_data).context(format!("写入 {page_path:?} 失败"))?;
+    }
+    Ok(())
+}
+
+pub fn count_pages(pdf_path: &Path) -> anyhow::Result<usize> {
+    let doc = Document::load(pdf_path).context(format!("读取 {pdf_path:?} 失败"))?;
+    Ok(doc.get_pages().len())
+}
+
+fn get_page_jpg_data(doc: &Document, page_id: ObjectId) -> anyhow::Result<&[u8]> {
+    let page = doc.get_dictionary(page_id)?;
+    let (_, resources) = doc.dereference(page.get(b"Resources")?)?;
+    let (_, xobjects) = doc.dereference(resources.as_dict()?.get(b"XObject")?)?;
+    let Some((_, img)) = xobjects.as_dict()?.iter().next() else {
+        return Err(anyhow!("该页没有图片"));
+    };
+    let (_, img) = doc.dereference(img)?;
+    let img_stream = img.as_stream()?;
+    let filter = img_stream.dict.get(b"Filter")?.as_name()?;
+    if filter != b"DCTDecode" {
+        let filter = String::from_utf8_lossy(filter);
+        return Err(anyhow!("图片的编码格式为 {filter}，不是jpg"));
+    }
+    Ok(&img_stream.content)
+}
+
+/// PDF中的文本字符串，用带BOM的UTF-16BE编码，以支持中文
+fn text_string(text: &str) -> Object {
+    let mut bytes = vec![0xFE, 0xFF];
+    for unit in text.encode_utf16() {
+        bytes.extend_from_slice(&unit.to_be_bytes());
+    }
+    Object::String(bytes, StringFormat::Hexadecimal)
+}
+
+struct PdfImage {
+    data: Vec<u8>,
+    width: u32,
+    height: u32,
+    color_space: &'static str,
+}
+
+impl PdfImage {
+    /// 灰度和RGB的jpg直接使用原始数据，其他图片(例如CMYK的jpg)先转换为RGB的jpg
+    fn load(img_path: &Path) -> anyhow::Result<Self> {
+        let data = std::fs::read(img_path)?;
+        if let Some((width, height, components)) = read_jpeg_header(&data) {
+            let color_space = match components {
+                1 => Some("DeviceGray"),
+                3 => Some("DeviceRGB"),
+                _ => None,
+            };
+            if let Some(color_space) = color_space {
+                return Ok(Self {
+                    data,
+                    width,
+                    height,
+                    color_space,
+                });
+            }
+        }
+
+        let img = image::load_from_memory(&data)?.into_rgb8();
+        let mut jpg_data = Cursor::new(Vec::new());
+        JpegEncoder::new_with_quality(&mut jpg_data, JPEG_QUALITY)
+            .encode_image(&img)
+            .map_err(|err| anyhow!("将图片转换为jpg失败: {err}"))?;
+        Ok(Self {
+            data: jpg_data.into_inner(),
+            width: img.width(),
+            height: img.height(),
+            color_space: "DeviceRGB",
+        })
+    }
+}
+
+/// 从jpg的SOF段中读取宽、高和颜色分量数，不是jpg或数据损坏时返回 `None`
+fn read_jpeg_header(data: &[u8]) -> Option<(u32, u32, u8)> {
+    if data.get(..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+    let mut pos = 2;
+    loop {
+        // 跳过段之间的填充字节
+        while *data.get(pos)? == 0xFF && *data.get(pos + 1)? == 0xFF {
+            pos += 1;
+        }
+        if *data.get(pos)? != 0xFF {
+            return None;
+        }
+        let marker = *data.get(pos + 1)?;
+        let segment_len = usize::from(u16::from_be_bytes([
+            *data.get(pos + 2)?,
+            *data.get(pos + 3)?,
+        ]));
+        // SOF0到SOF15，其中0xC4(DHT)、0xC8(JPG)和0xCC(DAC)不是SOF
+        if (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker) {
+            let height = u16::from_be_bytes([*data.get(pos + 5)?, *data.get(pos + 6)?]);
+            let width = u16::from_be_bytes([*data.get(pos + 7)?, *data.get(pos + 8)?]);
+            let components = *data.get(pos + 9)?;
+            return Some((u32::from(width), u32::from(height), components));
+        }
+        pos += 2 + segment_len;
+    }
+}
@@ -0,0 +1,168 @@
+use std::path::Path;
+
+use anyhow::Context;
+use parking_lot::RwLock;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use tauri_specta::Event;
+
+use crate::archive;
+use crate::config::Config;
+use crate::events;
+use crate::extensions::AnyhowErrorToStringChain;
+use crate::types::Comic;
+use crate::utils::filename_filter;
+
+/// 与 `save_series_metadata` 相同，但保存失败时只发送 `SaveSeriesMetadataErrorEvent`，不影响调用者
+pub fn try_save_series_metadata(app: &AppHandle, comic: &Comic) {
+    if let Err(err) = save_series_metadata(app, comic) {
+        let comic_title = comic.title.clone();
+        let err = err.context(format!("保存 {comic_title} 的系列元数据失败"));
+        emit_error_event(app, comic_title, err.to_string_chain());
+    }
+}
+
+/// 在漫画目录中保存Mylar3的 `series.json` 和 Tachiyomi/Mihon的 `details.json`
+///
+/// 只有漫画目录已经存在(即至少下载过一个章节)时才会保存，文件内容没有变化时不会重新写入
+pub fn save_series_metadata(app: &AppHandle, comic: &Comic) -> anyhow::Result<()> {
+    let download_dir = app.state::<RwLock<Config>>().read().download_dir.clone();
+    let comic_dir = download_dir.join(filename_filter(&comic.title));
+    if !comic_dir.is_dir() {
+        return Ok(());
+    }
+
+    let series_json = serde_json::to_string_pretty(&MylarSeries::from(comic))?;
+    save_if_changed(&comic_dir.join("series.json"), &series_json)?;
+
+    let details_json = serde_json::to_string_pretty(&TachiyomiDetails::from(comic))?;
+    save_if_changed(&comic_dir.join("details.json"), &details_json)?;
+
+    Ok(())
+}
+
+fn save_if_changed(path: &Path, content: &str) -> anyhow::Result<()> {
+    if std::fs::read_to_string(path).is_ok_and(|old_content| old_content == content) {
+        return Ok(());
+    }
+    archive::write_atomically(path, |temp_path| {
+        std::fs::write(temp_path, content).context(format!("写入 {temp_path:?} 失败"))
+    })
+}
+
+/// Mylar3的 `series.json`，格式见 <https://github.com/mylar3/mylar3/wiki/series.json-examples>
+#[derive(Serialize)]
+struct MylarSeries {
+    version: &'static str,
+    metadata: MylarSeriesMetadata,
+}
+
+#[derive(Serialize)]
+struct MylarSeriesMetadata {
+    #[serde(rename = "type")]
+    type_field: &'static str,
+    publisher: &'static str,
+    imprint: Option<String>,
+    name: String,
+    comicid: i64,
+    year: Option<i32>,
+    description_text: String,
+    description_formatted: Option<String>,
+    volume: Option<i64>,
+    booktype: &'static str,
+    age_rating: Option<String>,
+    collects: Option<Vec<String>>,
+    #[serde(rename = "ComicImage")]
+    comic_image: String,
+    total_issues: i64,
+    publication_run: Option<String>,
+    status: &'static str,
+}
+
+impl From<&Comic> for MylarSeries {
+    fn from(comic: &Comic) -> Self {
+        // `release_time` 的格式为 `2020-01-01` 或 `2020-01-01 00:00:00`
+        let year = comic
+            .release_time
+            .get(..4)
+            .and_then(|year| year.parse().ok());
+        let status = if comic.is_finish == 1 {
+            "Ended"
+        } else {
+            "Continuing"
+        };
+        MylarSeries {
+            version: "1.0.2",
+            metadata: MylarSeriesMetadata {
+                type_field: "comicSeries",
+                publisher: "哔哩哔哩漫画",
+                imprint: None,
+                name: comic.title.clone(),
+                comicid: comic.id,
+                year,
+                description_text: comic.evaluate.clone(),
+                description_formatted: None,
+                volume: None,
+                booktype: "Digital",
+                age_rating: None,
+                collects: None,
+                comic_image: comic.vertical_cover.clone(),
+                total_issues: comic.total,
+                publication_run: None,
+                status,
+            },
+        }
+    }
+}
+
+/// Tachiyomi/Mihon本地源的 `details.json`，格式见 <https://mihon.app/docs/guides/local-source/advanced>
+#[derive(Serialize)]
+struct TachiyomiDetails {
+    title: String,
+    author: String,
+    artist: String,
+    description: String,
+    genre: Vec<String>,
+    /// "0"表示未知，"1"表示连载中，"2"表示已完结
+    status: &'static str,
+    #[serde(rename = "_status values")]
+    status_values: [&'static str; 7],
+}
+
+impl From<&Comic> for TachiyomiDetails {
+    fn from(comic: &Comic) -> Self {
+        let status = if comic.is_finish == 1 { "2" } else { "1" };
+        let authors = comic
+            .authors
+            .iter()
+            .map(|author| author.name.as_str())
+            .collect::<Vec<&str>>()
+            .join(", ");
+        TachiyomiDetails {
+            title: comic.title.clone(),
+            author: authors.clone(),
+            artist: authors,
+            description: comic.evaluate.clone(),
+            genre: comic.styles.clone(),
+            status,
+            status_values: [
+                "0 = Unknown",
+                "1 = Ongoing",
+                "2 = Completed",
+                "3 = Licensed",
+                "4 = Publishing finished",
+                "5 = Cancelled",
+                "6 = On hiatus",
+            ],
+        }
+    }
+}
+
+pub fn emit_error_event(app: &AppHandle, comic_title: String, err_msg: String) {
+    let payload = events::SaveSeriesMetadataErrorEventPayload {
+        comic_title,
+        err_msg,
+    };
+    let event = events::SaveSeriesMetadataErrorEvent(payload);
+    let _ = event.emit(app);
+}
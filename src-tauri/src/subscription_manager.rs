@@ -0,0 +1,264 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use parking_lot::{Mutex, RwLock};
+use tauri::{AppHandle, Manager};
+use tauri_specta::Event;
+use tokio::sync::Notify;
+
+use crate::bili_client::BiliClient;
+use crate::config::Config;
+use crate::download_manager::DownloadManager;
+use crate::events;
+use crate::events::SubscriptionCheckResult;
+use crate::extensions::AnyhowErrorToStringChain;
+use crate::types::{EpisodeInfo, Subscription};
+
+/// 自动检查被关闭时，每隔多久重新读取一次配置
+const DISABLED_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 用于管理订阅的漫画，并定期将订阅漫画的新章节加入下载队列
+///
+/// 与 `DownloadManager` 一样，所有字段都被 `Arc` 包裹，克隆的开销极小
+#[derive(Clone)]
+pub struct SubscriptionManager {
+    app: AppHandle,
+    subscriptions: Arc<RwLock<Vec<Subscription>>>,
+    save_lock: Arc<Mutex<()>>,
+    // 检查间隔被修改时唤醒 `check_loop`，使新的间隔立即生效
+    interval_changed: Arc<Notify>,
+}
+
+impl SubscriptionManager {
+    pub fn new(app: &AppHandle) -> anyhow::Result<Self> {
+        let manager = SubscriptionManager {
+            app: app.clone(),
+            subscriptions: Arc::new(RwLock::new(Vec::new())),
+            save_lock: Arc::new(Mutex::new(())),
+            interval_changed: Arc::new(Notify::new()),
+        };
+        let subscriptions_path = manager.subscriptions_path()?;
+        if subscriptions_path.exists() {
+            let subscriptions_string = std::fs::read_to_string(&subscriptions_path)
+                .context(format!("读取 {subscriptions_path:?} 失败"))?;
+            // 如果文件损坏无法解析，则视为没有订阅任何漫画
+            let subscriptions: Vec<Subscription> =
+                serde_json::from_str(&subscriptions_string).unwrap_or_default();
+            *manager.subscriptions.write() = subscriptions;
+        }
+
+        tauri::async_runtime::spawn(Self::check_loop(
+            app.clone(),
+            manager.interval_changed.clone(),
+        ));
+
+        Ok(manager)
+    }
+
+    pub async fn subscribe(&self, comic_id: i64) -> anyhow::Result<Subscription> {
+        if self.is_subscribed(comic_id) {
+            return Err(anyhow!("漫画 `{comic_id}` 已订阅"));
+        }
+        let comic = self
+            .bili_client()
+            .get_comic(comic_id)
+            .await
+            .context(format!("获取漫画 `{comic_id}` 的信息失败"))?;
+        let subscription = Subscription {
+            comic_id,
+            comic_title: comic.title,
+            subscribed_at: chrono::Local::now().timestamp(),
+            last_checked_at: None,
+            // 订阅时已有的章节不会被自动下载
+            known_episode_ids: Some(comic.episode_infos.iter().map(|ep| ep.episode_id).collect()),
+        };
+        {
+            let mut subscriptions = self.subscriptions.write();
+            // 获取漫画信息期间可能已经订阅过了
+            if subscriptions.iter().any(|sub| sub.comic_id == comic_id) {
+                return Err(anyhow!("漫画 `{comic_id}` 已订阅"));
+            }
+            subscriptions.push(subscription.clone());
+        }
+        self.save()?;
+        Ok(subscription)
+    }
+
+    pub fn unsubscribe(&self, comic_id: i64) -> anyhow::Result<()> {
+        {
+            let mut subscriptions = self.subscriptions.write();
+            let len = subscriptions.len();
+            subscriptions.retain(|sub| sub.comic_id != comic_id);
+            if subscriptions.len() == len {
+                return Err(anyhow!("漫画 `{comic_id}` 未订阅"));
+            }
+        }
+        self.save()?;
+        Ok(())
+    }
+
+    pub fn list_subscriptions(&self) -> Vec<Subscription> {
+        self.subscriptions.read().clone()
+    }
+
+    fn is_subscribed(&self, comic_id: i64) -> bool {
+        self.subscriptions
+            .read()
+            .iter()
+            .any(|sub| sub.comic_id == comic_id)
+    }
+
+    /// 检查间隔被修改后调用，让新的间隔立即生效
+    pub fn notify_interval_changed(&self) {
+        self.interval_changed.notify_one();
+    }
+
+    async fn check_loop(app: AppHandle, interval_changed: Arc<Notify>) {
+        loop {
+            let interval_minutes = app
+                .state::<RwLock<Config>>()
+                .read()
+                .subscription_check_interval_minutes;
+            let interval = if interval_minutes == 0 {
+                DISABLED_CHECK_INTERVAL
+            } else {
+                Duration::from_secs(interval_minutes * 60)
+            };
+            tokio::select! {
+                () = tokio::time::sleep(interval) => {},
+                // 间隔被修改，按新的间隔重新计时
+                () = interval_changed.notified() => continue,
+            }
+            if interval_minutes == 0 {
+                continue;
+            }
+            let manager = app.state::<SubscriptionManager>().inner().clone();
+            manager.check_subscriptions().await;
+        }
+    }
+
+    /// 检查所有订阅的漫画，将订阅后新增的、还没下载的已解锁章节加入下载队列，然后发送 `SubscriptionCheckEndEvent`
+    pub async fn check_subscriptions(&self) {
+        let comic_ids: Vec<i64> = self
+            .subscriptions
+            .read()
+            .iter()
+            .map(|sub| sub.comic_id)
+            .collect();
+        let mut results = Vec::with_capacity(comic_ids.len());
+        for comic_id in comic_ids {
+            let result = match self.check_subscription(comic_id).await {
+                Ok(result) => result,
+                Err(err) => {
+                    let comic_title = self.comic_title(comic_id).unwrap_or_default();
+                    let err = err.context(format!("检查订阅的漫画 {comic_title} 失败"));
+                    SubscriptionCheckResult {
+                        comic_id,
+                        comic_title,
+                        queued_episode_titles: vec![],
+                        err_msg: Some(err.to_string_chain()),
+                    }
+                }
+            };
+            results.push(result);
+        }
+        // 检查结果已经通过事件发送，保存失败只会导致下次启动时 `last_checked_at` 不准确
+        let _ = self.save();
+        emit_check_end_event(&self.app, results);
+    }
+
+    async fn check_subscription(&self, comic_id: i64) -> anyhow::Result<SubscriptionCheckResult> {
+        let comic = self.bili_client().get_comic(comic_id).await?;
+        let download_manager = self.app.state::<DownloadManager>();
+        let known_episode_ids = self
+            .subscriptions
+            .read()
+            .iter()
+            .find(|sub| sub.comic_id == comic_id)
+            .and_then(|sub| sub.known_episode_ids.clone());
+        let mut queued_episode_titles = Vec::new();
+        let known_episode_ids = match known_episode_ids {
+            // 旧版本的订阅没有记录已知的章节，这次只记录已有的章节，避免把所有旧章节都加入下载队列
+            None => comic.episode_infos.iter().map(|ep| ep.episode_id).collect(),
+            Some(mut known_episode_ids) => {
+                // 还没解锁的新章节不会被记录，等它解锁后再加入下载队列
+                // `is_downloaded` 是根据本地已下载的文件判断的
+                let new_episodes: Vec<EpisodeInfo> = comic
+                    .episode_infos
+                    .iter()
+                    .filter(|ep| !ep.is_locked && !known_episode_ids.contains(&ep.episode_id))
+                    .cloned()
+                    .collect();
+                let mut queued_episodes = Vec::new();
+                for ep in new_episodes {
+                    known_episode_ids.push(ep.episode_id);
+                    if ep.is_downloaded || download_manager.contains_task(ep.episode_id) {
+                        continue;
+                    }
+                    queued_episode_titles.push(ep.episode_title.clone());
+                    queued_episodes.push(ep);
+                }
+                download_manager.submit_episodes(queued_episodes).await?;
+                known_episode_ids
+            }
+        };
+        // 更新订阅的漫画标题、检查时间和已知的章节
+        if let Some(sub) = self
+            .subscriptions
+            .write()
+            .iter_mut()
+            .find(|sub| sub.comic_id == comic_id)
+        {
+            sub.comic_title.clone_from(&comic.title);
+            sub.last_checked_at = Some(chrono::Local::now().timestamp());
+            sub.known_episode_ids = Some(known_episode_ids);
+        }
+        Ok(SubscriptionCheckResult {
+            comic_id,
+            comic_title: comic.title,
+            queued_episode_titles,
+            err_msg: None,
+        })
+    }
+
+    fn comic_title(&self, comic_id: i64) -> Option<String> {
+        self.subscriptions
+            .read()
+            .iter()
+            .find(|sub| sub.comic_id == comic_id)
+            .map(|sub| sub.comic_title.clone())
+    }
+
+    /// 将订阅列表保存到 `subscriptions.json`
+    fn save(&self) -> anyhow::Result<()> {
+        // 保证快照与写入的顺序一致，避免旧的快照覆盖新的快照
+        let _guard = self.save_lock.lock();
+        let subscriptions_string = serde_json::to_string_pretty(&*self.subscriptions.read())?;
+        let subscriptions_path = self.subscriptions_path()?;
+        // 先写入临时文件再重命名，避免写入过程中崩溃导致文件损坏
+        let temp_subscriptions_path = subscriptions_path.with_extension("json.tmp");
+        std::fs::write(&temp_subscriptions_path, subscriptions_string)
+            .context(format!("写入 {temp_subscriptions_path:?} 失败"))?;
+        std::fs::rename(&temp_subscriptions_path, &subscriptions_path).context(format!(
+            "将 {temp_subscriptions_path:?} 重命名为 {subscriptions_path:?} 失败"
+        ))?;
+        Ok(())
+    }
+
+    fn subscriptions_path(&self) -> anyhow::Result<PathBuf> {
+        let app_data_dir = self.app.path().app_data_dir()?;
+        Ok(app_data_dir.join("subscriptions.json"))
+    }
+
+    fn bili_client(&self) -> BiliClient {
+        self.app.state::<BiliClient>().inner().clone()
+    }
+}
+
+fn emit_check_end_event(app: &AppHandle, comics: Vec<SubscriptionCheckResult>) {
+    let payload = events::SubscriptionCheckEndEventPayload { comics };
+    let event = events::SubscriptionCheckEndEvent(payload);
+    let _ = event.emit(app);
+}
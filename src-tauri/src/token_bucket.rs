@@ -0,0 +1,50 @@
+use std::time::{Duration, Instant};
+
+use tokio::sync::Mutex;
+
+/// 令牌桶，用于限制多个任务共享的速率(例如下载速度)
+///
+/// 桶的容量为1秒的速率，允许短时间的突发。令牌不足时允许透支，透支的部分通过等待来偿还
+pub struct TokenBucket {
+    state: Mutex<TokenBucketState>,
+}
+
+struct TokenBucketState {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub fn new() -> Self {
+        let state = TokenBucketState {
+            tokens: 0.0,
+            last_refill: Instant::now(),
+        };
+        Self {
+            state: Mutex::new(state),
+        }
+    }
+
+    /// 消耗 `amount` 个令牌，`rate` 为每秒补充的令牌数，为0表示不限速
+    ///
+    /// 等待期间会一直持有锁，保证所有调用方按顺序获取令牌
+    #[allow(clippy::cast_precision_loss)]
+    pub async fn acquire(&self, amount: u64, rate: u64) {
+        if rate == 0 {
+            return;
+        }
+        let rate = rate as f64;
+        let mut state = self.state.lock().await;
+        // 根据距离上次补充令牌的时间补充令牌，但不超过桶的容量
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * rate).min(rate);
+        state.last_refill = now;
+        // 透支令牌，然后等待透支的部分被补充回来
+        state.tokens -= amount as f64;
+        if state.tokens < 0.0 {
+            let wait_secs = -state.tokens / rate;
+            tokio::time::sleep(Duration::from_secs_f64(wait_secs)).await;
+        }
+    }
+}
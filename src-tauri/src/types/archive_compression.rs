@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use zip::CompressionMethod;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub enum ArchiveCompression {
+    /// 不压缩，适合本身已经压缩过的jpg图片，阅读器读取也更快
+    #[default]
+    Stored,
+    Deflate,
+}
+impl ArchiveCompression {
+    pub fn compression_method(&self) -> CompressionMethod {
+        match self {
+            ArchiveCompression::Stored => CompressionMethod::Stored,
+            ArchiveCompression::Deflate => CompressionMethod::Deflated,
+        }
+    }
+}
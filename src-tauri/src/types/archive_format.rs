@@ -7,6 +7,8 @@ pub enum ArchiveFormat {
     Image,
     Zip,
     Cbz,
+    Pdf,
+    Epub,
 }
 impl ArchiveFormat {
     pub fn extension(&self) -> &str {
@@ -14,6 +16,8 @@ impl ArchiveFormat {
             ArchiveFormat::Image => "",
             ArchiveFormat::Zip => "zip",
             ArchiveFormat::Cbz => "cbz",
+            ArchiveFormat::Pdf => "pdf",
+            ArchiveFormat::Epub => "epub",
         }
     }
 }
@@ -1,11 +1,10 @@
 use std::collections::HashMap;
 
-use crate::config::Config;
-use crate::responses::{ComicRespData, EpisodeRespData};
+use crate::library::Library;
+use crate::responses::{AuthorRespData, ComicRespData, EpisodeRespData};
 use crate::utils::filename_filter;
 
 use chrono::{Datelike, NaiveDateTime};
-use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use specta::Type;
 use tauri::{AppHandle, Manager};
@@ -157,6 +156,14 @@ impl Comic {
     // TODO: 统一用from实现，以减少代码行数
     pub fn from(app: &AppHandle, comic: ComicRespData) -> Self {
         let comic_title = filename_filter(&comic.title);
+        let is_right_to_left = Self::get_is_right_to_left(comic.orientation, comic.japan_comic);
+        let tags = comic
+            .tags
+            .iter()
+            .map(|tag| tag.name.as_str())
+            .collect::<Vec<&str>>()
+            .join(", ");
+        let penciller = Self::get_penciller(&comic.authors);
         let mut episode_infos: Vec<EpisodeInfo> = comic
             .ep_list
             .into_iter()
@@ -168,7 +175,7 @@ impl Comic {
                 let pub_time = NaiveDateTime::parse_from_str(&ep.pub_time, TIME_FORMAT).ok()?;
 
                 let comic_info = ComicInfo {
-                    manga: "Yes".to_string(),
+                    manga: ComicInfo::get_manga(is_right_to_left),
                     series: comic_title.clone(),
                     publisher: "哔哩哔哩漫画".to_string(),
                     writer: comic
@@ -182,10 +189,19 @@ impl Comic {
                     count: comic.total,
                     title: episode_title.clone(),
                     number: ep.ord.to_string(),
+                    volume: None,
                     page_count: ep.image_count,
                     year: pub_time.year(),
                     month: pub_time.month(),
                     day: pub_time.day(),
+                    web: format!("https://manga.bilibili.com/mc{}/{}", comic.id, ep.id),
+                    tags: tags.clone(),
+                    alternate_series: comic.comic_alias.join(", "),
+                    language_iso: "zh".to_string(),
+                    penciller: penciller.clone(),
+                    notes: comic.introduction.clone(),
+                    age_rating: "Unknown".to_string(),
+                    pages: None,
                 };
 
                 let episode_info = EpisodeInfo {
@@ -195,6 +211,11 @@ impl Comic {
                     comic_title,
                     is_locked: ep.is_locked,
                     is_downloaded,
+                    ord: ep.ord,
+                    is_free: ep.pay_gold == 0 || ep.is_in_free,
+                    vertical_cover: comic.vertical_cover.clone(),
+                    is_right_to_left,
+                    episode_cover: ep.cover.clone(),
                     comic_info,
                 };
                 Some(episode_info)
@@ -396,20 +417,38 @@ impl Comic {
         };
         ep_title.trim().to_string()
     }
+    /// 作者的 `cname` 中包含"画"的是作画者，没有区分作画者时认为所有作者都参与了作画
+    fn get_penciller(authors: &[AuthorRespData]) -> String {
+        let pencillers: Vec<&str> = authors
+            .iter()
+            .filter(|author| author.cname.contains('画'))
+            .map(|author| author.name.as_str())
+            .collect();
+        if pencillers.is_empty() {
+            return authors
+                .iter()
+                .map(|author| author.name.as_str())
+                .collect::<Vec<&str>>()
+                .join(", ");
+        }
+        pencillers.join(", ")
+    }
+    /// `orientation` 为1表示从左往右，为2表示从右往左，其他值表示未指定，此时日漫默认从右往左
+    fn get_is_right_to_left(orientation: i64, japan_comic: bool) -> bool {
+        match orientation {
+            1 => false,
+            2 => true,
+            _ => japan_comic,
+        }
+    }
     fn get_is_downloaded(app: &AppHandle, ep_title: &str, comic_title: &str) -> bool {
-        let config = app.state::<RwLock<Config>>();
-        let config = config.read();
-        config
-            .download_dir
-            .join(comic_title)
-            .join(ep_title)
-            .with_extension(config.archive_format.extension())
-            .exists()
+        app.state::<Library>().is_downloaded(comic_title, ep_title)
     }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
 #[serde(rename_all = "camelCase")]
+#[allow(clippy::struct_excessive_bools)]
 pub struct EpisodeInfo {
     pub episode_id: i64,
     pub episode_title: String,
@@ -417,6 +456,21 @@ pub struct EpisodeInfo {
     pub comic_title: String,
     pub is_locked: bool,
     pub is_downloaded: bool,
+    // 旧版本保存的下载任务中没有以下字段
+    #[serde(default)]
+    pub ord: f64,
+    /// 免费章节或限时免费中的章节
+    #[serde(default)]
+    pub is_free: bool,
+    /// 漫画的竖版封面
+    #[serde(default)]
+    pub vertical_cover: String,
+    /// 漫画是否从右往左阅读
+    #[serde(default)]
+    pub is_right_to_left: bool,
+    /// 章节的封面
+    #[serde(default)]
+    pub episode_cover: String,
     pub comic_info: ComicInfo,
 }
 
@@ -443,6 +497,10 @@ pub struct ComicInfo {
     pub title: String,
     #[yaserde(rename = "Number")]
     pub number: String,
+    /// 只有合并章节得到的单行本才有卷号
+    #[yaserde(rename = "Volume")]
+    #[serde(default)]
+    pub volume: Option<i64>,
     #[yaserde(rename = "PageCount")]
     pub page_count: i64,
     #[yaserde(rename = "Year")]
@@ -451,6 +509,75 @@ pub struct ComicInfo {
     pub month: u32,
     #[yaserde(rename = "Day")]
     pub day: u32,
+    // 旧版本保存的下载任务中没有以下字段
+    /// 章节的网页链接
+    #[yaserde(rename = "Web")]
+    #[serde(default)]
+    pub web: String,
+    #[yaserde(rename = "Tags")]
+    #[serde(default)]
+    pub tags: String,
+    /// 漫画的别名
+    #[yaserde(rename = "AlternateSeries")]
+    #[serde(default)]
+    pub alternate_series: String,
+    #[yaserde(rename = "LanguageISO")]
+    #[serde(default)]
+    pub language_iso: String,
+    #[yaserde(rename = "Penciller")]
+    #[serde(default)]
+    pub penciller: String,
+    /// 漫画的简介
+    #[yaserde(rename = "Notes")]
+    #[serde(default)]
+    pub notes: String,
+    #[yaserde(rename = "AgeRating")]
+    #[serde(default)]
+    pub age_rating: String,
+    /// 每页的尺寸和大小，保存章节时才会填写
+    #[yaserde(rename = "Pages")]
+    #[serde(default)]
+    pub pages: Option<ComicPages>,
+}
+impl ComicInfo {
+    /// `Manga` 为 `YesAndRightToLeft` 时表示从右往左阅读
+    pub fn get_manga(is_right_to_left: bool) -> String {
+        if is_right_to_left {
+            "YesAndRightToLeft".to_string()
+        } else {
+            "Yes".to_string()
+        }
+    }
+
+    pub fn is_right_to_left(&self) -> bool {
+        self.manga == "YesAndRightToLeft"
+    }
+}
+
+#[derive(
+    Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type, YaSerialize, YaDeserialize,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct ComicPages {
+    #[yaserde(rename = "Page")]
+    pub pages: Vec<ComicPageInfo>,
+}
+
+#[derive(
+    Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type, YaSerialize, YaDeserialize,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct ComicPageInfo {
+    /// 页的索引，从0开始
+    #[yaserde(attribute, rename = "Image")]
+    pub image: u32,
+    #[yaserde(attribute, rename = "ImageWidth")]
+    pub image_width: u32,
+    #[yaserde(attribute, rename = "ImageHeight")]
+    pub image_height: u32,
+    /// 图片文件的字节数
+    #[yaserde(attribute, rename = "ImageSize")]
+    pub image_size: u64,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
@@ -0,0 +1,21 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+/// 校验时发现无法正常阅读的章节，它已被重命名为以 `CORRUPTED_PREFIX` 开头，之后不再被视为已下载
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct CorruptedEpisode {
+    /// 漫画目录名，即过滤后的漫画标题
+    pub comic_title: String,
+    pub episode_title: String,
+    /// 从 `ComicInfo.xml` 的 `Web` 中解析出的漫画id，可以用来获取漫画信息并重新下载，无法解析时为 `None`
+    pub comic_id: Option<i64>,
+    /// 从 `ComicInfo.xml` 的 `Web` 中解析出的章节id，无法解析时为 `None`
+    pub episode_id: Option<i64>,
+    /// 重命名后的路径，重命名失败时为原路径
+    pub path: PathBuf,
+    /// 发现的所有问题，如无法解码的图片、缺页、尺寸不符等
+    pub problems: Vec<String>,
+}
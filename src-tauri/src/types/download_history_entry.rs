@@ -0,0 +1,29 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::types::ArchiveFormat;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadHistoryEntry {
+    pub comic_id: i64,
+    pub comic_title: String,
+    pub episode_id: i64,
+    pub episode_title: String,
+    pub archive_format: ArchiveFormat,
+    /// 章节保存的路径，下载失败时为 `None`
+    pub path: Option<PathBuf>,
+    pub page_count: u32,
+    /// 下载成功时为保存的文件或目录的大小，下载失败时为本次下载的字节数
+    pub bytes: u64,
+    /// 开始下载的时间戳，单位为秒，还没开始下载就失败了则为 `None`
+    pub started_at: Option<i64>,
+    /// 下载结束的时间戳，单位为秒
+    pub finished_at: i64,
+    /// 下载失败时的错误链，下载成功时为 `None`
+    pub err_msg: Option<String>,
+    /// 重试次数用完后仍然下载失败的页码，从1开始
+    pub failed_pages: Vec<u32>,
+}
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::types::DownloadHistoryEntry;
+
+/// 查询下载历史的条件，未指定的条件不参与筛选
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DownloadHistoryQuery {
+    pub comic_id: Option<i64>,
+    pub episode_id: Option<i64>,
+    /// 只查询下载失败的记录
+    pub failed_only: bool,
+    /// 只查询在该时间戳(含)之后结束的记录，单位为秒
+    pub since: Option<i64>,
+    /// 只查询在该时间戳(含)之前结束的记录，单位为秒
+    pub until: Option<i64>,
+    /// 最多返回的记录数量
+    pub limit: Option<usize>,
+}
+
+impl DownloadHistoryQuery {
+    pub fn matches(&self, entry: &DownloadHistoryEntry) -> bool {
+        if self
+            .comic_id
+            .is_some_and(|comic_id| entry.comic_id != comic_id)
+        {
+            return false;
+        }
+        if self
+            .episode_id
+            .is_some_and(|episode_id| entry.episode_id != episode_id)
+        {
+            return false;
+        }
+        if self.failed_only && entry.err_msg.is_none() {
+            return false;
+        }
+        if self.since.is_some_and(|since| entry.finished_at < since) {
+            return false;
+        }
+        if self.until.is_some_and(|until| entry.finished_at > until) {
+            return false;
+        }
+        true
+    }
+}
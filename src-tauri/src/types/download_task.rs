@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::types::{DownloadTaskState, EpisodeInfo};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadTask {
+    pub ep_info: EpisodeInfo,
+    pub state: DownloadTaskState,
+}
@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::types::EpisodeInfo;
+
+/// 下载整部漫画时用于筛选章节的条件，未指定的条件不参与筛选
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(default, rename_all = "camelCase")]
+pub struct EpisodeFilter {
+    /// 只下载已解锁的章节
+    pub unlocked_only: bool,
+    /// 只下载还没下载过的章节
+    pub not_downloaded_only: bool,
+    /// 只下载免费的章节
+    pub free_only: bool,
+    /// 只下载 `ord` 大于等于该值的章节
+    pub min_ord: Option<f64>,
+    /// 只下载 `ord` 小于等于该值的章节
+    pub max_ord: Option<f64>,
+}
+
+impl EpisodeFilter {
+    pub fn matches(&self, ep_info: &EpisodeInfo) -> bool {
+        if self.unlocked_only && ep_info.is_locked {
+            return false;
+        }
+        if self.not_downloaded_only && ep_info.is_downloaded {
+            return false;
+        }
+        if self.free_only && !ep_info.is_free {
+            return false;
+        }
+        if self.min_ord.is_some_and(|min_ord| ep_info.ord < min_ord) {
+            return false;
+        }
+        if self.max_ord.is_some_and(|max_ord| ep_info.ord > max_ord) {
+            return false;
+        }
+        true
+    }
+}
@@ -0,0 +1,19 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::types::LibraryEpisode;
+
+/// 本地漫画库中的一部漫画
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryComic {
+    /// 漫画目录名，即过滤后的漫画标题
+    pub comic_title: String,
+    pub comic_dir: PathBuf,
+    /// 按名称自然排序的章节和单行本
+    pub episodes: Vec<LibraryEpisode>,
+    /// 所有章节和单行本的大小之和
+    pub bytes: u64,
+}
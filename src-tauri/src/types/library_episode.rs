@@ -0,0 +1,26 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::types::{ArchiveFormat, ComicInfo};
+
+/// 本地漫画库中的一个章节或单行本
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryEpisode {
+    /// 章节名或单行本名，即去掉扩展名后的文件名
+    pub name: String,
+    pub path: PathBuf,
+    pub archive_format: ArchiveFormat,
+    /// 实际包含的图片数量
+    pub page_count: u32,
+    /// 文件或目录的大小
+    pub bytes: u64,
+    /// 从章节中读回的 `ComicInfo.xml`，pdf和epub中没有 `ComicInfo.xml`，此时为 `None`
+    pub comic_info: Option<ComicInfo>,
+    /// 是否不完整，下载中或下载失败残留的临时目录、图片数量少于 `ComicInfo.page_count` 以及无法读取的章节都是不完整的
+    pub is_incomplete: bool,
+    /// 读取章节失败时的错误链
+    pub err_msg: Option<String>,
+}
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+/// 处理已下载的漫画时的范围
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub enum LibraryScope {
+    /// 下载目录中的所有漫画
+    #[default]
+    All,
+    /// 下载目录中的某部漫画，值为漫画目录名，即过滤后的漫画标题
+    Comic(String),
+}
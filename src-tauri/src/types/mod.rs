@@ -1,13 +1,37 @@
+mod archive_compression;
 mod archive_format;
 mod check_update_result;
 mod comic;
+mod corrupted_episode;
+mod download_history_entry;
+mod download_history_query;
+mod download_task;
+mod download_task_state;
+mod episode_filter;
+mod library_comic;
+mod library_episode;
+mod library_scope;
 mod proxy_mode;
+mod subscription;
+mod volume_options;
 mod web_qrcode_data;
 
+pub use archive_compression::*;
 pub use archive_format::*;
 pub use check_update_result::*;
 pub use comic::*;
+pub use corrupted_episode::*;
+pub use download_history_entry::*;
+pub use download_history_query::*;
+pub use download_task::*;
+pub use download_task_state::*;
+pub use episode_filter::*;
+pub use library_comic::*;
+pub use library_episode::*;
+pub use library_scope::*;
 pub use proxy_mode::*;
+pub use subscription::*;
+pub use volume_options::*;
 pub use web_qrcode_data::*;
 
 pub type AsyncRwLock<T> = tokio::sync::RwLock<T>;
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct Subscription {
+    pub comic_id: i64,
+    pub comic_title: String,
+    /// 订阅时的时间戳，单位为秒
+    pub subscribed_at: i64,
+    /// 上次检查更新的时间戳，单位为秒，还没检查过则为 `None`
+    pub last_checked_at: Option<i64>,
+    /// 已知的章节id，只有不在其中的新章节才会被加入下载队列，为 `None` 时下次检查只记录已有的章节
+    #[serde(default)]
+    pub known_episode_ids: Option<Vec<i64>>,
+}
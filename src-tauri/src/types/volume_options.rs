@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::types::{ArchiveFormat, EpisodeInfo};
+
+/// 将多个章节合并为单行本时的选项
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct VolumeOptions {
+    /// 卷号，会写入 `ComicInfo.xml` 的 `Volume`
+    pub volume: i64,
+    /// 要合并的章节id，为空时按 `min_ord` 和 `max_ord` 选择章节
+    #[serde(default)]
+    pub episode_ids: Vec<i64>,
+    /// 合并 `ord` 大于等于该值的章节
+    #[serde(default)]
+    pub min_ord: Option<f64>,
+    /// 合并 `ord` 小于等于该值的章节
+    #[serde(default)]
+    pub max_ord: Option<f64>,
+    /// 单行本的格式，为 `None` 时使用配置中的下载格式
+    #[serde(default)]
+    pub archive_format: Option<ArchiveFormat>,
+}
+
+impl VolumeOptions {
+    pub fn selects(&self, ep_info: &EpisodeInfo) -> bool {
+        if !self.episode_ids.is_empty() {
+            return self.episode_ids.contains(&ep_info.episode_id);
+        }
+        if self.min_ord.is_some_and(|min_ord| ep_info.ord < min_ord) {
+            return false;
+        }
+        if self.max_ord.is_some_and(|max_ord| ep_info.ord > max_ord) {
+            return false;
+        }
+        true
+    }
+}
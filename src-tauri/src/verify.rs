@@ -0,0 +1,259 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use tauri::{AppHandle, Manager};
+use tauri_specta::Event;
+use zip::ZipArchive;
+
+use crate::archive;
+use crate::archive::PageSize;
+use crate::events;
+use crate::extensions::AnyhowErrorToStringChain;
+use crate::library::Library;
+use crate::types::{
+    ArchiveFormat, ComicInfo, ComicPageInfo, CorruptedEpisode, LibraryEpisode, LibraryScope,
+};
+
+/// 解码后的一页图片，解码失败时 `size` 为解码失败的原因
+struct DecodedPage {
+    name: String,
+    size: anyhow::Result<PageSize>,
+}
+
+/// 校验 `scope` 范围内已下载的所有cbz、zip和文件夹格式的章节，返回无法正常阅读的章节，并发送 `VerifyLibraryProgressEvent`
+///
+/// 页数和图片尺寸都来自章节中的 `ComicInfo.xml`，不需要联网，其中没有 `Pages` 时只检查页数，
+/// 无法正常阅读的章节会被加上 `CORRUPTED_PREFIX` 重命名，之后不再被视为已下载，可以重新下载
+pub async fn verify_library(
+    app: &AppHandle,
+    scope: LibraryScope,
+) -> anyhow::Result<Vec<CorruptedEpisode>> {
+    let comics = {
+        let app = app.clone();
+        tauri::async_runtime::spawn_blocking(move || app.state::<Library>().scan(&scope)).await??
+    };
+    // 临时下载目录和已经损坏的章节都不需要校验
+    let targets: Vec<(String, LibraryEpisode)> = comics
+        .into_iter()
+        .flat_map(|comic| {
+            let comic_title = comic.comic_title;
+            comic
+                .episodes
+                .into_iter()
+                .filter(is_verifiable)
+                .map(move |ep| (comic_title.clone(), ep))
+        })
+        .collect();
+
+    #[allow(clippy::cast_possible_truncation)]
+    let total = targets.len() as u32;
+    let mut corrupted_episodes = Vec::new();
+    for (i, (comic_title, library_ep)) in targets.into_iter().enumerate() {
+        let comic_info = library_ep.comic_info.clone().unwrap_or_default();
+        let ids = get_ids(&comic_info.web);
+        let page_sizes = get_page_sizes(&comic_info);
+        // 章节中没有 `ComicInfo.xml` 时页数为0，不检查是否缺页
+        let expected_page_count = comic_info.page_count;
+        let path = library_ep.path.clone();
+        let archive_format = library_ep.archive_format.clone();
+        let mut problems = tauri::async_runtime::spawn_blocking(move || {
+            verify_archive(&path, &archive_format, expected_page_count, &page_sizes)
+        })
+        .await?;
+
+        #[allow(clippy::cast_possible_truncation)]
+        let current = i as u32 + 1;
+        emit_progress_event(
+            app,
+            comic_title.clone(),
+            library_ep.name.clone(),
+            current,
+            total,
+        );
+        if problems.is_empty() {
+            continue;
+        }
+        let path = match app
+            .state::<Library>()
+            .mark_corrupted(&library_ep.path, problems.join("\n"))
+        {
+            Ok(corrupted_path) => corrupted_path,
+            Err(err) => {
+                let err = err.context("标记为损坏失败，该章节依然会被视为已下载");
+                problems.push(err.to_string_chain());
+                library_ep.path
+            }
+        };
+        corrupted_episodes.push(CorruptedEpisode {
+            comic_title,
+            episode_title: library_ep.name,
+            comic_id: ids.map(|(comic_id, _)| comic_id),
+            episode_id: ids.map(|(_, episode_id)| episode_id),
+            path,
+            problems,
+        });
+    }
+    Ok(corrupted_episodes)
+}
+
+/// 从 `ComicInfo.xml` 的 `Web` 中解析漫画id和章节id，格式为 `https://manga.bilibili.com/mc{comic_id}/{episode_id}`
+fn get_ids(web: &str) -> Option<(i64, i64)> {
+    let (comic_id, episode_id) = web
+        .strip_prefix("https://manga.bilibili.com/mc")?
+        .split_once('/')?;
+    Some((comic_id.parse().ok()?, episode_id.parse().ok()?))
+}
+
+/// `ComicInfo.xml` 的 `Pages` 中记录的图片尺寸，顺序与压缩包中的图片一致，没有 `Pages` 时返回空列表
+fn get_page_sizes(comic_info: &ComicInfo) -> Vec<PageSize> {
+    let Some(comic_pages) = &comic_info.pages else {
+        return vec![];
+    };
+    let mut pages: Vec<&ComicPageInfo> = comic_pages.pages.iter().collect();
+    pages.sort_by_key(|page| page.image);
+    pages
+        .into_iter()
+        .map(|page| PageSize {
+            width: page.image_width,
+            height: page.image_height,
+        })
+        .collect()
+}
+
+/// 校验 `archive_path` 能否正常阅读，返回发现的问题，没有问题时返回空列表
+///
+/// 逐页解码所有图片，图片数量少于 `expected_page_count` 时视为缺页，
+/// 图片数量与 `page_sizes` 一致时，尺寸已知的页还会与解码后的尺寸比较
+pub fn verify_archive(
+    archive_path: &Path,
+    archive_format: &ArchiveFormat,
+    expected_page_count: i64,
+    page_sizes: &[PageSize],
+) -> Vec<String> {
+    let pages = match decode_pages(archive_path, archive_format) {
+        Ok(pages) => pages,
+        Err(err) => return vec![err.to_string_chain()],
+    };
+
+    let mut problems = Vec::new();
+    for page in &pages {
+        if let Err(err) = &page.size {
+            problems.push(format!(
+                "图片 {} 无法解码: {}",
+                page.name,
+                err.to_string_chain()
+            ));
+        }
+    }
+    let page_count = pages.len();
+    if i64::try_from(page_count).unwrap_or(i64::MAX) < expected_page_count {
+        problems.push(format!(
+            "只有 {page_count} 张图片，少于 {expected_page_count} 页"
+        ));
+    }
+    // 缺页时无法确定图片与页的对应关系
+    if page_count != page_sizes.len() {
+        return problems;
+    }
+    for (expected_size, page) in page_sizes.iter().zip(&pages) {
+        let Ok(size) = &page.size else {
+            continue;
+        };
+        if expected_size.width == 0 || expected_size.height == 0 || size == expected_size {
+            continue;
+        }
+        problems.push(format!(
+            "图片 {} 的尺寸为 {}x{}，应为 {}x{}",
+            page.name, size.width, size.height, expected_size.width, expected_size.height
+        ));
+    }
+    problems
+}
+
+/// 只有cbz、zip和文件夹格式的章节能够校验，pdf和epub在保存时已经重新编排过图片
+fn is_verifiable(library_ep: &LibraryEpisode) -> bool {
+    matches!(
+        library_ep.archive_format,
+        ArchiveFormat::Image | ArchiveFormat::Zip | ArchiveFormat::Cbz
+    ) && !library_ep
+        .path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+/// 按页的顺序解码 `archive_path` 中的所有图片
+fn decode_pages(
+    archive_path: &Path,
+    archive_format: &ArchiveFormat,
+) -> anyhow::Result<Vec<DecodedPage>> {
+    if *archive_format == ArchiveFormat::Image {
+        let page_paths: Vec<PathBuf> = archive::get_all_page_paths(archive_path)?;
+        let pages = page_paths
+            .iter()
+            .map(|page_path| DecodedPage {
+                name: page_path
+                    .strip_prefix(archive_path)
+                    .unwrap_or(page_path)
+                    .to_string_lossy()
+                    .to_string(),
+                size: image::open(page_path)
+                    .map(|img| PageSize {
+                        width: img.width(),
+                        height: img.height(),
+                    })
+                    .map_err(anyhow::Error::from),
+            })
+            .collect();
+        return Ok(pages);
+    }
+
+    let zip_file = File::open(archive_path).context(format!("打开 {archive_path:?} 失败"))?;
+    let mut zip_archive =
+        ZipArchive::new(zip_file).context(format!("读取 {archive_path:?} 失败"))?;
+    let entry_names = archive::get_zip_page_names(&zip_archive, |_| true);
+    let mut pages = Vec::with_capacity(entry_names.len());
+    for entry_name in entry_names {
+        let size = read_zip_entry(&mut zip_archive, &entry_name).and_then(|data| {
+            let img = image::load_from_memory(&data)?;
+            Ok(PageSize {
+                width: img.width(),
+                height: img.height(),
+            })
+        });
+        pages.push(DecodedPage {
+            name: entry_name,
+            size,
+        });
+    }
+    Ok(pages)
+}
+
+fn read_zip_entry(zip_archive: &mut ZipArchive<File>, entry_name: &str) -> anyhow::Result<Vec<u8>> {
+    let mut entry = zip_archive
+        .by_name(entry_name)
+        .context(format!("读取 {entry_name} 失败"))?;
+    let mut data = Vec::new();
+    entry
+        .read_to_end(&mut data)
+        .context(format!("解压 {entry_name} 失败"))?;
+    Ok(data)
+}
+
+fn emit_progress_event(
+    app: &AppHandle,
+    comic_title: String,
+    episode_title: String,
+    current: u32,
+    total: u32,
+) {
+    let payload = events::VerifyLibraryProgressEventPayload {
+        comic_title,
+        episode_title,
+        current,
+        total,
+    };
+    let event = events::VerifyLibraryProgressEvent(payload);
+    let _ = event.emit(app);
+}
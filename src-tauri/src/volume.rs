@@ -0,0 +1,210 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context};
+use parking_lot::RwLock;
+use tauri::{AppHandle, Manager};
+
+use crate::archive;
+use crate::archive::PageSize;
+use crate::config::Config;
+use crate::epub::{EpubBuilder, EpubMetadata};
+use crate::library::Library;
+use crate::pdf::PdfBuilder;
+use crate::types::{ArchiveFormat, ComicInfo, EpisodeInfo};
+
+/// 查找已下载的章节时依次尝试的格式
+const ARCHIVE_FORMATS: [ArchiveFormat; 5] = [
+    ArchiveFormat::Image,
+    ArchiveFormat::Zip,
+    ArchiveFormat::Cbz,
+    ArchiveFormat::Pdf,
+    ArchiveFormat::Epub,
+];
+
+/// 将已下载的 `episodes` 按顺序合并为第 `volume` 卷单行本，返回单行本的路径
+///
+/// 章节可以是任意格式，单行本中每个章节对应一个子目录(文件夹、zip、cbz)、书签(pdf)或目录项(epub)，
+/// `cover` 只在保存为epub时使用
+pub fn merge_episodes(
+    app: &AppHandle,
+    episodes: &[EpisodeInfo],
+    volume: i64,
+    archive_format: &ArchiveFormat,
+    cover: Option<Vec<u8>>,
+) -> anyhow::Result<PathBuf> {
+    let Some(first_ep) = episodes.first() else {
+        return Err(anyhow!("没有要合并的章节"));
+    };
+    let download_dir = app.state::<RwLock<Config>>().read().download_dir.clone();
+    let comic_dir = download_dir.join(&first_ep.comic_title);
+    let volume_name = get_volume_name(&first_ep.comic_title, volume);
+    // 以 `.合并中-` 开头，表示是临时目录
+    let temp_volume_dir = comic_dir.join(format!(".合并中-{volume_name}"));
+    if temp_volume_dir.exists() {
+        std::fs::remove_dir_all(&temp_volume_dir)
+            .context(format!("删除 {temp_volume_dir:?} 失败"))?;
+    }
+    std::fs::create_dir_all(&temp_volume_dir)
+        .context(format!("创建目录 {temp_volume_dir:?} 失败"))?;
+
+    let volume_path = archive::get_archive_path(&comic_dir, &volume_name, archive_format);
+    let volume_info = VolumeInfo {
+        episodes,
+        volume,
+        comic_dir: &comic_dir,
+        cover,
+    };
+    if let Err(err) = save_volume(
+        app,
+        &volume_info,
+        archive_format,
+        &temp_volume_dir,
+        &volume_path,
+    ) {
+        // 合并失败，临时目录已经没有用了
+        let _ = std::fs::remove_dir_all(&temp_volume_dir);
+        return Err(err);
+    }
+    app.state::<Library>()
+        .index_archive(&volume_path, archive_format);
+    Ok(volume_path)
+}
+
+struct VolumeInfo<'a> {
+    episodes: &'a [EpisodeInfo],
+    volume: i64,
+    comic_dir: &'a Path,
+    cover: Option<Vec<u8>>,
+}
+
+fn save_volume(
+    app: &AppHandle,
+    volume_info: &VolumeInfo,
+    archive_format: &ArchiveFormat,
+    temp_volume_dir: &Path,
+    volume_path: &Path,
+) -> anyhow::Result<()> {
+    let VolumeInfo {
+        episodes,
+        volume,
+        comic_dir,
+        ..
+    } = *volume_info;
+    let (compression, compression_level) = {
+        let config = app.state::<RwLock<Config>>();
+        let config = config.read();
+        (
+            config.archive_compression.clone(),
+            config.archive_compression_level,
+        )
+    };
+    // 将每个章节的图片提取到各自的子目录中，子目录名以序号开头以保证章节的顺序
+    let mut chapters = Vec::with_capacity(episodes.len());
+    let mut page_count = 0;
+    for (i, ep) in episodes.iter().enumerate() {
+        let episode_title = &ep.episode_title;
+        let Some((ep_path, ep_format)) = find_episode(comic_dir, episode_title) else {
+            return Err(anyhow!("章节 {episode_title} 还没有下载"));
+        };
+        let chapter_dir = temp_volume_dir.join(format!("{:03} {episode_title}", i + 1));
+        std::fs::create_dir_all(&chapter_dir).context(format!("创建目录 {chapter_dir:?} 失败"))?;
+        archive::extract_pages(&ep_path, &ep_format, &chapter_dir)
+            .context(format!("提取 {ep_path:?} 中的图片失败"))?;
+        let page_paths = archive::get_sorted_page_paths(&chapter_dir)?;
+        page_count += page_paths.len();
+        chapters.push((episode_title.clone(), page_paths));
+    }
+
+    let first_comic_info = &episodes[0].comic_info;
+    let comic_info = ComicInfo {
+        title: format!("第{volume}卷"),
+        number: volume.to_string(),
+        volume: Some(volume),
+        page_count: i64::try_from(page_count).unwrap_or(i64::MAX),
+        ..first_comic_info.clone()
+    };
+
+    match archive_format {
+        ArchiveFormat::Image => {
+            let comic_info = archive::comic_info_with_pages(&comic_info, temp_volume_dir, &[])?;
+            let comic_info_path = temp_volume_dir.join(archive::COMIC_INFO_FILENAME);
+            std::fs::write(&comic_info_path, archive::to_comic_info_xml(&comic_info)?)
+                .context(format!("写入 {comic_info_path:?} 失败"))?;
+            archive::save_image_dir(temp_volume_dir, volume_path)?;
+            return Ok(());
+        }
+        ArchiveFormat::Zip | ArchiveFormat::Cbz => {
+            archive::save_zip(
+                temp_volume_dir,
+                &comic_info,
+                volume_path,
+                &compression,
+                compression_level,
+                &[],
+            )?;
+            return Ok(());
+        }
+        ArchiveFormat::Pdf => {
+            let mut pdf_builder = PdfBuilder::new();
+            let mut outlines = Vec::with_capacity(chapters.len());
+            for (episode_title, page_paths) in &chapters {
+                for (i, page_path) in page_paths.iter().enumerate() {
+                    let page_id = pdf_builder.add_page(page_path, PageSize::default())?;
+                    // 书签跳转到章节的第一页
+                    if i == 0 {
+                        outlines.push((episode_title.clone(), page_id));
+                    }
+                }
+            }
+            pdf_builder.add_outlines(&outlines);
+            pdf_builder.save(&comic_info, volume_path)?;
+        }
+        ArchiveFormat::Epub => {
+            let first_ep = &episodes[0];
+            let epub_metadata = EpubMetadata {
+                identifier: format!("bilibili-manga-{}-volume-{volume}", first_ep.comic_id),
+                comic_info,
+                cover: volume_info.cover.clone(),
+                is_right_to_left: first_ep.is_right_to_left,
+            };
+            let mut epub_builder = EpubBuilder::new(&epub_metadata);
+            for (episode_title, page_paths) in &chapters {
+                epub_builder.add_chapter(episode_title, page_paths, &[])?;
+            }
+            let options = archive::zip_file_options(&compression, compression_level);
+            epub_builder.save(volume_path, options)?;
+        }
+    }
+
+    std::fs::remove_dir_all(temp_volume_dir).context(format!("删除 {temp_volume_dir:?} 失败"))?;
+    Ok(())
+}
+
+pub fn get_volume_name(comic_title: &str, volume: i64) -> String {
+    format!("{comic_title} 第{volume}卷")
+}
+
+/// 在 `comic_dir` 中查找已合并的单行本，返回单行本的路径和格式
+pub fn find_volume(comic_dir: &Path, volume_name: &str) -> Option<(PathBuf, ArchiveFormat)> {
+    find_archive(comic_dir, volume_name)
+}
+
+/// 在 `comic_dir` 中查找已下载的章节，返回章节的路径和格式
+///
+/// 章节的格式不一定与当前配置的格式相同，所以依次尝试所有格式
+fn find_episode(comic_dir: &Path, episode_title: &str) -> Option<(PathBuf, ArchiveFormat)> {
+    find_archive(comic_dir, episode_title)
+}
+
+/// 依次尝试所有格式，在 `comic_dir` 中查找名为 `name` 的章节或单行本
+fn find_archive(comic_dir: &Path, name: &str) -> Option<(PathBuf, ArchiveFormat)> {
+    ARCHIVE_FORMATS.into_iter().find_map(|archive_format| {
+        let path = archive::get_archive_path(comic_dir, name, &archive_format);
+        let exists = if archive_format == ArchiveFormat::Image {
+            path.is_dir()
+        } else {
+            path.is_file()
+        };
+        exists.then_some((path, archive_format))
+    })
+}
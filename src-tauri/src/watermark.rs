@@ -0,0 +1,249 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context};
+use image::codecs::jpeg::JpegEncoder;
+use image::{Rgb, RgbImage};
+use tauri::AppHandle;
+use tauri_specta::Event;
+
+use crate::events;
+use crate::extensions::AnyhowErrorToStringChain;
+
+/// 水印区域的宽度占图片宽度的比例
+const WATERMARK_WIDTH_RATIO: f64 = 0.25;
+/// 水印区域的高度占图片宽度的比例，水印的大小随图片宽度缩放
+const WATERMARK_HEIGHT_RATIO: f64 = 0.06;
+/// 判断两个像素颜色是否相同时，每个通道允许的最大差值
+const COLOR_TOLERANCE: u8 = 16;
+/// 水印是半透明的白色文字和黑色描边，不透明度不会超过这个值，完全不透明的线条一定是画面内容
+const WATERMARK_MAX_ALPHA: f64 = 0.75;
+/// 同一像素在各个通道上算出的不透明度允许的最大差值，超过时说明该像素不是水印与背景混合而成的
+const ALPHA_TOLERANCE: f64 = 0.1;
+/// 水印与水印区域的上边和左边之间至少留有的空白，占水印区域宽高的比例
+const WATERMARK_MARGIN_RATIO: f64 = 0.05;
+/// 水印文字至少横跨水印区域宽度的比例
+const WATERMARK_MIN_SPAN_RATIO: f64 = 0.4;
+/// 水印像素占水印区域面积的比例范围，超出范围的不是水印
+const WATERMARK_COVERAGE_RANGE: std::ops::RangeInclusive<f64> = 0.005..=0.5;
+/// 重新编码图片时使用的jpg质量
+const JPEG_QUALITY: u8 = 95;
+
+/// 去除 `dir_path` 中所有jpg图片右下角的水印，并发送 `RemoveWatermark*Event`
+///
+/// 单张图片处理失败只会发送 `RemoveWatermarkErrorEvent`，不会中断其他图片的处理，
+/// 但处理结束后如果有图片处理失败，则返回错误
+pub fn remove_watermark(app: &AppHandle, dir_path: &Path) -> anyhow::Result<()> {
+    let mut img_paths: Vec<PathBuf> = std::fs::read_dir(dir_path)
+        .context(format!("读取目录 {dir_path:?} 失败"))?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "jpg"))
+        .collect();
+    img_paths.sort();
+
+    #[allow(clippy::cast_possible_truncation)]
+    let total = img_paths.len() as u32;
+    emit_start_event(app, dir_path.to_path_buf(), total);
+    let mut current = 0;
+    let mut failed = 0;
+    for img_path in img_paths {
+        if let Err(err) = remove_image_watermark(&img_path) {
+            failed += 1;
+            let err_msg = err.to_string_chain();
+            emit_error_event(app, dir_path.to_path_buf(), img_path, err_msg);
+            continue;
+        }
+        current += 1;
+        emit_success_event(app, dir_path.to_path_buf(), img_path, current);
+    }
+    emit_end_event(app, dir_path.to_path_buf());
+    if failed > 0 {
+        return Err(anyhow!(
+            "{dir_path:?} 中有 {failed}/{total} 张图片去除水印失败"
+        ));
+    }
+    Ok(())
+}
+
+/// 去除单张图片的水印，只有检测到水印时才会覆盖原图片，返回是否检测到水印
+fn remove_image_watermark(img_path: &Path) -> anyhow::Result<bool> {
+    let mut img = image::open(img_path)
+        .context(format!("打开图片 {img_path:?} 失败"))?
+        .into_rgb8();
+    if !fill_watermark_region(&mut img) {
+        return Ok(false);
+    }
+    // 先写入临时文件再重命名，避免写入过程中崩溃导致原图片损坏
+    let temp_img_path = img_path.with_extension("jpg.tmp");
+    let file = File::create(&temp_img_path).context(format!("创建 {temp_img_path:?} 失败"))?;
+    let encoder = JpegEncoder::new_with_quality(BufWriter::new(file), JPEG_QUALITY);
+    img.write_with_encoder(encoder)
+        .context(format!("保存图片 {temp_img_path:?} 失败"))?;
+    std::fs::rename(&temp_img_path, img_path)
+        .context(format!("将 {temp_img_path:?} 重命名为 {img_path:?} 失败"))?;
+    Ok(true)
+}
+
+/// 检测图片右下角的水印区域，如果检测到水印则用背景色填充该区域，返回是否检测到水印
+///
+/// 只有同时满足以下条件时才认为是水印，否则说明画面内容延伸到了右下角，为了不破坏画面，不做任何处理：
+/// - 水印区域上方一行和左侧一列像素的颜色一致，即水印叠加在纯色背景上
+/// - 水印区域中与背景色不同的每个像素，都能由白色或黑色以不超过 `WATERMARK_MAX_ALPHA` 的不透明度与背景色混合而成
+/// - 这些像素与水印区域的上边和左边留有空白，横跨的宽度和占的面积都与水印文字相符
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+fn fill_watermark_region(img: &mut RgbImage) -> bool {
+    let (width, height) = img.dimensions();
+    let region_width = (f64::from(width) * WATERMARK_WIDTH_RATIO) as u32;
+    let region_height = (f64::from(width) * WATERMARK_HEIGHT_RATIO) as u32;
+    if region_width == 0 || region_height == 0 || region_width >= width || region_height >= height {
+        return false;
+    }
+    let x0 = width - region_width;
+    let y0 = height - region_height;
+    // 水印区域上方一行和左侧一列的像素
+    let border_pixels: Vec<Rgb<u8>> = (x0 - 1..width)
+        .map(|x| *img.get_pixel(x, y0 - 1))
+        .chain((y0..height).map(|y| *img.get_pixel(x0 - 1, y)))
+        .collect();
+    let Some(background) = median_color(&border_pixels) else {
+        return false;
+    };
+    if !border_pixels
+        .iter()
+        .all(|pixel| is_same_color(*pixel, background))
+    {
+        return false;
+    }
+
+    let mut watermark_pixel_count = 0_u64;
+    let (mut min_x, mut min_y, mut max_x) = (width, height, x0);
+    for y in y0..height {
+        for x in x0..width {
+            let pixel = *img.get_pixel(x, y);
+            if is_same_color(pixel, background) {
+                continue;
+            }
+            if !is_watermark_blend(pixel, background) {
+                return false;
+            }
+            watermark_pixel_count += 1;
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+        }
+    }
+    // 水印区域与背景色一致，说明没有水印
+    if watermark_pixel_count == 0 {
+        return false;
+    }
+    let margin_x = (f64::from(region_width) * WATERMARK_MARGIN_RATIO) as u32;
+    let margin_y = (f64::from(region_height) * WATERMARK_MARGIN_RATIO) as u32;
+    if min_x < x0 + margin_x || min_y < y0 + margin_y {
+        return false;
+    }
+    let span = f64::from(max_x - min_x + 1) / f64::from(region_width);
+    #[allow(clippy::cast_precision_loss)]
+    let coverage =
+        watermark_pixel_count as f64 / (f64::from(region_width) * f64::from(region_height));
+    if span < WATERMARK_MIN_SPAN_RATIO || !WATERMARK_COVERAGE_RANGE.contains(&coverage) {
+        return false;
+    }
+
+    for y in y0..height {
+        for x in x0..width {
+            img.put_pixel(x, y, background);
+        }
+    }
+    true
+}
+
+/// `pixel` 是否能由白色或黑色以不超过 `WATERMARK_MAX_ALPHA` 的不透明度与 `background` 混合而成
+fn is_watermark_blend(pixel: Rgb<u8>, background: Rgb<u8>) -> bool {
+    [Rgb([255, 255, 255]), Rgb([0, 0, 0])]
+        .into_iter()
+        .any(|overlay| get_blend_alpha(pixel, background, overlay).is_some())
+}
+
+/// 计算 `overlay` 以多大的不透明度与 `background` 混合才能得到 `pixel`，无法混合得到时返回 `None`
+fn get_blend_alpha(pixel: Rgb<u8>, background: Rgb<u8>, overlay: Rgb<u8>) -> Option<f64> {
+    let mut alphas = Vec::with_capacity(3);
+    for ((p, b), o) in pixel.0.into_iter().zip(background.0).zip(overlay.0) {
+        let (p, b, o) = (f64::from(p), f64::from(b), f64::from(o));
+        // 背景色在该通道上已经与水印颜色相同，混合后该通道不会变化
+        if (o - b).abs() <= f64::from(COLOR_TOLERANCE) {
+            if (p - b).abs() > f64::from(COLOR_TOLERANCE) {
+                return None;
+            }
+            continue;
+        }
+        alphas.push((p - b) / (o - b));
+    }
+    let min_alpha = alphas.iter().copied().fold(f64::INFINITY, f64::min);
+    let max_alpha = alphas.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    if alphas.is_empty() || max_alpha - min_alpha > ALPHA_TOLERANCE {
+        return None;
+    }
+    let alpha = f64::midpoint(min_alpha, max_alpha);
+    (0.0..=WATERMARK_MAX_ALPHA)
+        .contains(&alpha)
+        .then_some(alpha)
+}
+
+/// 每个通道分别取中位数，避免少量噪点影响背景色的判断
+fn median_color(pixels: &[Rgb<u8>]) -> Option<Rgb<u8>> {
+    if pixels.is_empty() {
+        return None;
+    }
+    let mut channels = [Vec::new(), Vec::new(), Vec::new()];
+    for pixel in pixels {
+        for (channel, value) in channels.iter_mut().zip(pixel.0) {
+            channel.push(value);
+        }
+    }
+    let median = channels.map(|mut channel| {
+        channel.sort_unstable();
+        channel[channel.len() / 2]
+    });
+    Some(Rgb(median))
+}
+
+fn is_same_color(a: Rgb<u8>, b: Rgb<u8>) -> bool {
+    a.0.iter()
+        .zip(b.0)
+        .all(|(x, y)| x.abs_diff(y) <= COLOR_TOLERANCE)
+}
+
+fn emit_start_event(app: &AppHandle, dir_path: PathBuf, total: u32) {
+    let payload = events::RemoveWatermarkStartEventPayload { dir_path, total };
+    let event = events::RemoveWatermarkStartEvent(payload);
+    let _ = event.emit(app);
+}
+
+fn emit_success_event(app: &AppHandle, dir_path: PathBuf, img_path: PathBuf, current: u32) {
+    let payload = events::RemoveWatermarkSuccessEventPayload {
+        dir_path,
+        img_path,
+        current,
+    };
+    let event = events::RemoveWatermarkSuccessEvent(payload);
+    let _ = event.emit(app);
+}
+
+fn emit_error_event(app: &AppHandle, dir_path: PathBuf, img_path: PathBuf, err_msg: String) {
+    let payload = events::RemoveWatermarkErrorEventPayload {
+        dir_path,
+        img_path,
+        err_msg,
+    };
+    let event = events::RemoveWatermarkErrorEvent(payload);
+    let _ = event.emit(app);
+}
+
+fn emit_end_event(app: &AppHandle, dir_path: PathBuf) {
+    let payload = events::RemoveWatermarkEndEventPayload { dir_path };
+    let event = events::RemoveWatermarkEndEvent(payload);
+    let _ = event.emit(app);
+}
@@ -59,6 +59,46 @@ async downloadEpisodes(episodes: EpisodeInfo[]) : Promise<Result<null, CommandEr
     else return { status: "error", error: e  as any };
 }
 },
+/**
+ * 获取漫画的所有章节，将符合 `filter` 的章节加入下载队列，返回加入下载队列的章节
+ * 
+ * 已在下载队列中的章节会被跳过
+ */
+async downloadComic(comicId: number, filter: EpisodeFilter) : Promise<Result<EpisodeInfo[], CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("download_comic", { comicId, filter }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async getDownloadTasks() : Promise<DownloadTask[]> {
+    return await TAURI_INVOKE("get_download_tasks");
+},
+async pauseDownload(episodeId: number) : Promise<Result<null, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("pause_download", { episodeId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async resumeDownload(episodeId: number) : Promise<Result<null, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("resume_download", { episodeId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async cancelDownload(episodeId: number) : Promise<Result<null, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("cancel_download", { episodeId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
 async showPathInFileManager(path: string) : Promise<Result<null, CommandError>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("show_path_in_file_manager", { path }) };
@@ -75,6 +115,122 @@ async getUserProfile() : Promise<Result<UserProfileRespData, CommandError>> {
     else return { status: "error", error: e  as any };
 }
 },
+async subscribe(comicId: number) : Promise<Result<Subscription, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("subscribe", { comicId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async unsubscribe(comicId: number) : Promise<Result<null, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("unsubscribe", { comicId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async listSubscriptions() : Promise<Subscription[]> {
+    return await TAURI_INVOKE("list_subscriptions");
+},
+/**
+ * 查询符合条件的下载历史，最新的记录排在最前面
+ */
+async getDownloadHistory(query: DownloadHistoryQuery) : Promise<Result<DownloadHistoryEntry[], CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_download_history", { query }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async clearDownloadHistory() : Promise<Result<null, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("clear_download_history") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * 去除 `dir_path` 中所有图片的水印，处理进度通过 `RemoveWatermark*Event` 发送
+ */
+async removeWatermark(dirPath: string) : Promise<Result<null, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("remove_watermark", { dirPath }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * 将漫画 `comic_id` 中按 `options` 选择的已下载章节合并为单行本，返回单行本的路径
+ */
+async mergeEpisodes(comicId: number, options: VolumeOptions) : Promise<Result<string, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("merge_episodes", { comicId, options }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * 导出漫画 `comic_id` 的Calibre元数据，`volume` 为 `None` 时导出整部漫画的，否则导出第 `volume` 卷单行本的
+ * 
+ * 返回导出的OPF文件的路径
+ */
+async exportOpf(comicId: number, volume: number | null) : Promise<Result<string, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("export_opf", { comicId, volume }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * 在后台将 `scope` 范围内以 `from` 格式保存的章节和单行本转换为 `to` 格式，转换进度通过 `ConvertLibrary*Event` 发送
+ * 
+ * 同一时间只能进行一次转换，下载队列中有章节的漫画会被跳过
+ */
+async convertLibrary(from: ArchiveFormat, to: ArchiveFormat, scope: LibraryScope) : Promise<Result<null, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("convert_library", { from, to, scope }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * 重新扫描 `scope` 范围内已下载的漫画并更新本地漫画库的索引，返回扫描到的漫画
+ */
+async scanLibrary(scope: LibraryScope) : Promise<Result<LibraryComic[], CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("scan_library", { scope }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * 获取本地漫画库索引中的所有漫画，只包含已经扫描过的漫画
+ */
+async getLibrary() : Promise<LibraryComic[]> {
+    return await TAURI_INVOKE("get_library");
+},
+/**
+ * 离线校验 `scope` 范围内已下载的章节能否正常阅读，返回无法正常阅读的章节，校验进度通过 `VerifyLibraryProgressEvent` 发送
+ * 
+ * 返回的章节已被重命名，之后会被视为未下载，重新获取漫画信息后即可重新下载，重新下载成功后损坏的章节会被删除
+ */
+async verifyLibrary(scope: LibraryScope) : Promise<Result<CorruptedEpisode[], CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("verify_library", { scope }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
 async checkUpdate() : Promise<Result<CheckUpdateResult, CommandError>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("check_update") };
@@ -89,29 +245,53 @@ async checkUpdate() : Promise<Result<CheckUpdateResult, CommandError>> {
 
 
 export const events = __makeEvents__<{
+convertLibraryEndEvent: ConvertLibraryEndEvent,
+convertLibraryErrorEvent: ConvertLibraryErrorEvent,
+convertLibraryStartEvent: ConvertLibraryStartEvent,
+convertLibrarySuccessEvent: ConvertLibrarySuccessEvent,
+downloadCancelEvent: DownloadCancelEvent,
 downloadEndEvent: DownloadEndEvent,
 downloadImageErrorEvent: DownloadImageErrorEvent,
 downloadImageSuccessEvent: DownloadImageSuccessEvent,
+downloadPauseEvent: DownloadPauseEvent,
 downloadPendingEvent: DownloadPendingEvent,
+downloadResumeEvent: DownloadResumeEvent,
 downloadSpeedEvent: DownloadSpeedEvent,
 downloadStartEvent: DownloadStartEvent,
+recordDownloadHistoryErrorEvent: RecordDownloadHistoryErrorEvent,
 removeWatermarkEndEvent: RemoveWatermarkEndEvent,
 removeWatermarkErrorEvent: RemoveWatermarkErrorEvent,
 removeWatermarkStartEvent: RemoveWatermarkStartEvent,
 removeWatermarkSuccessEvent: RemoveWatermarkSuccessEvent,
-setProxyErrorEvent: SetProxyErrorEvent
+restoreDownloadTasksErrorEvent: RestoreDownloadTasksErrorEvent,
+saveSeriesMetadataErrorEvent: SaveSeriesMetadataErrorEvent,
+setProxyErrorEvent: SetProxyErrorEvent,
+subscriptionCheckEndEvent: SubscriptionCheckEndEvent,
+verifyLibraryProgressEvent: VerifyLibraryProgressEvent
 }>({
+convertLibraryEndEvent: "convert-library-end-event",
+convertLibraryErrorEvent: "convert-library-error-event",
+convertLibraryStartEvent: "convert-library-start-event",
+convertLibrarySuccessEvent: "convert-library-success-event",
+downloadCancelEvent: "download-cancel-event",
 downloadEndEvent: "download-end-event",
 downloadImageErrorEvent: "download-image-error-event",
 downloadImageSuccessEvent: "download-image-success-event",
+downloadPauseEvent: "download-pause-event",
 downloadPendingEvent: "download-pending-event",
+downloadResumeEvent: "download-resume-event",
 downloadSpeedEvent: "download-speed-event",
 downloadStartEvent: "download-start-event",
+recordDownloadHistoryErrorEvent: "record-download-history-error-event",
 removeWatermarkEndEvent: "remove-watermark-end-event",
 removeWatermarkErrorEvent: "remove-watermark-error-event",
 removeWatermarkStartEvent: "remove-watermark-start-event",
 removeWatermarkSuccessEvent: "remove-watermark-success-event",
-setProxyErrorEvent: "set-proxy-error-event"
+restoreDownloadTasksErrorEvent: "restore-download-tasks-error-event",
+saveSeriesMetadataErrorEvent: "save-series-metadata-error-event",
+setProxyErrorEvent: "set-proxy-error-event",
+subscriptionCheckEndEvent: "subscription-check-end-event",
+verifyLibraryProgressEvent: "verify-library-progress-event"
 })
 
 /** user-defined constants **/
@@ -120,7 +300,12 @@ setProxyErrorEvent: "set-proxy-error-event"
 
 /** user-defined types **/
 
-export type ArchiveFormat = "Image" | "Zip" | "Cbz"
+export type ArchiveCompression = 
+/**
+ * 不压缩，适合本身已经压缩过的jpg图片，阅读器读取也更快
+ */
+"Stored" | "Deflate"
+export type ArchiveFormat = "Image" | "Zip" | "Cbz" | "Pdf" | "Epub"
 export type Author = { id: number; name: string; cname: string }
 export type AutoPayInfo = { auto_pay_orders: AutoPayOrder[]; id: number }
 export type AutoPayOrder = { id: number; title: string }
@@ -128,29 +313,311 @@ export type BannerRespData = { icon: string; title: string; url: string }
 export type CheckUpdateResult = { normalVersions: string[]; importantVersions: string[] }
 export type Comic = { id: number; title: string; comic_type: number; page_default: number; page_allow: number; horizontal_cover: string; square_cover: string; vertical_cover: string; author_name: string[]; styles: string[]; last_ord: number; is_finish: number; status: number; fav: number; read_order: number; evaluate: string; total: number; episodeInfos: EpisodeInfo[]; release_time: string; is_limit: number; read_epid: number; last_read_time: string; is_download: number; read_short_title: string; styles2: Styles2[]; renewal_time: string; last_short_title: string; discount_type: number; discount: number; discount_end: string; no_reward: boolean; batch_discount_type: number; ep_discount_type: number; has_fav_activity: boolean; fav_free_amount: number; allow_wait_free: boolean; wait_hour: number; wait_free_at: string; no_danmaku: number; auto_pay_status: number; no_month_ticket: boolean; immersive: boolean; no_discount: boolean; show_type: number; pay_mode: number; classic_lines: string; pay_for_new: number; fav_comic_info: FavComicInfo; serial_status: number; album_count: number; wiki_id: number; disable_coupon_amount: number; japan_comic: boolean; interact_value: string; temporary_finish_time: string; introduction: string; comment_status: number; no_screenshot: boolean; type: number; no_rank: boolean; presale_text: string; presale_discount: number; no_leaderboard: boolean; auto_pay_info: AutoPayInfo; orientation: number; story_elems: StoryElem[]; tags: Tag[]; is_star_hall: number; hall_icon_text: string; rookie_fav_tip: RookieFavTip; authors: Author[]; comic_alias: string[]; horizontal_covers: string[]; data_info: DataInfo; last_short_title_msg: string }
 export type ComicInSearchRespData = { id: number; title: string; square_cover: string; vertical_cover: string; author_name: string[]; styles: string[]; is_finish: number; allow_wait_free: boolean; discount_type: number; type: number; wiki: WikiRespData }
-export type ComicInfo = { manga: string; series: string; publisher: string; writer: string; genre: string; summary: string; count: number; title: string; number: string; pageCount: number; year: number; month: number; day: number }
+export type ComicInfo = { manga: string; series: string; publisher: string; writer: string; genre: string; summary: string; count: number; title: string; number: string; 
+/**
+ * 只有合并章节得到的单行本才有卷号
+ */
+volume: number | null; pageCount: number; year: number; month: number; day: number; 
+/**
+ * 章节的网页链接
+ */
+web: string; tags: string; 
+/**
+ * 漫画的别名
+ */
+alternateSeries: string; languageIso: string; penciller: string; 
+/**
+ * 漫画的简介
+ */
+notes: string; ageRating: string; 
+/**
+ * 每页的尺寸和大小，保存章节时才会填写
+ */
+pages: ComicPages | null }
+export type ComicPageInfo = { 
+/**
+ * 页的索引，从0开始
+ */
+image: number; imageWidth: number; imageHeight: number; 
+/**
+ * 图片文件的字节数
+ */
+imageSize: number }
+export type ComicPages = { pages: ComicPageInfo[] }
 export type CommandError = string
-export type Config = { cookie: string; downloadDir: string; archiveFormat: ArchiveFormat; lastUpdateCheckTs: number; proxyMode: ProxyMode; proxyHost: string; proxyPort: number }
+export type Config = { cookie: string; downloadDir: string; archiveFormat: ArchiveFormat; archiveCompression: ArchiveCompression; 
+/**
+ * 只对Deflate有效，范围为1到9
+ */
+archiveCompressionLevel: number; lastUpdateCheckTs: number; proxyMode: ProxyMode; proxyHost: string; proxyPort: number; episodeConcurrency: number; imageConcurrency: number; imageIntervalMinMs: number; imageIntervalMaxMs: number; imageTokenBatchSize: number; imageRetryAttempts: number; imageRetryBackoffMs: number; 
+/**
+ * 所有下载共享的速度上限，单位为字节/秒，为0表示不限速
+ */
+downloadSpeedLimit: number; removeWatermarkBeforeArchive: boolean; 
+/**
+ * 在漫画目录中保存漫画的封面 `cover.jpg`
+ */
+saveComicCover: boolean; 
+/**
+ * 将章节封面作为第0页放在cbz的最前面
+ */
+prependEpisodeCover: boolean; 
+/**
+ * 在漫画目录中保存 `series.json` 和 `details.json`，每次获取漫画信息时更新
+ */
+saveSeriesMetadata: boolean; 
+/**
+ * 为0表示不自动检查订阅的漫画
+ */
+subscriptionCheckIntervalMinutes: number }
+export type ConvertLibraryEndEvent = ConvertLibraryEndEventPayload
+export type ConvertLibraryEndEventPayload = { converted: number; failed: number }
+export type ConvertLibraryErrorEvent = ConvertLibraryErrorEventPayload
+export type ConvertLibraryErrorEventPayload = { srcPath: string; errMsg: string }
+export type ConvertLibraryStartEvent = ConvertLibraryStartEventPayload
+export type ConvertLibraryStartEventPayload = { total: number }
+export type ConvertLibrarySuccessEvent = ConvertLibrarySuccessEventPayload
+export type ConvertLibrarySuccessEventPayload = { srcPath: string; destPath: string; current: number }
+/**
+ * 校验时发现无法正常阅读的章节，它已被重命名为以 `CORRUPTED_PREFIX` 开头，之后不再被视为已下载
+ */
+export type CorruptedEpisode = { 
+/**
+ * 漫画目录名，即过滤后的漫画标题
+ */
+comicTitle: string; episodeTitle: string; 
+/**
+ * 从 `ComicInfo.xml` 的 `Web` 中解析出的漫画id，可以用来获取漫画信息并重新下载，无法解析时为 `None`
+ */
+comicId: number | null; 
+/**
+ * 从 `ComicInfo.xml` 的 `Web` 中解析出的章节id，无法解析时为 `None`
+ */
+episodeId: number | null; 
+/**
+ * 重命名后的路径，重命名失败时为原路径
+ */
+path: string; 
+/**
+ * 发现的所有问题，如无法解码的图片、缺页、尺寸不符等
+ */
+problems: string[] }
 export type DataInfo = { read_score: ReadScore; interactive_value: InteractiveValue }
+export type DownloadCancelEvent = DownloadCancelEventPayload
+export type DownloadCancelEventPayload = { id: number }
 export type DownloadEndEvent = DownloadEndEventPayload
-export type DownloadEndEventPayload = { id: number; errMsg: string | null }
+export type DownloadEndEventPayload = { id: number; errMsg: string | null; 
+/**
+ * 重试次数用完后仍然下载失败的页码，从1开始
+ */
+failedPages: number[] }
+export type DownloadHistoryEntry = { comicId: number; comicTitle: string; episodeId: number; episodeTitle: string; archiveFormat: ArchiveFormat; 
+/**
+ * 章节保存的路径，下载失败时为 `None`
+ */
+path: string | null; pageCount: number; 
+/**
+ * 下载成功时为保存的文件或目录的大小，下载失败时为本次下载的字节数
+ */
+bytes: number; 
+/**
+ * 开始下载的时间戳，单位为秒，还没开始下载就失败了则为 `None`
+ */
+startedAt: number | null; 
+/**
+ * 下载结束的时间戳，单位为秒
+ */
+finishedAt: number; 
+/**
+ * 下载失败时的错误链，下载成功时为 `None`
+ */
+errMsg: string | null; 
+/**
+ * 重试次数用完后仍然下载失败的页码，从1开始
+ */
+failedPages: number[] }
+/**
+ * 查询下载历史的条件，未指定的条件不参与筛选
+ */
+export type DownloadHistoryQuery = { comicId: number | null; episodeId: number | null; 
+/**
+ * 只查询下载失败的记录
+ */
+failedOnly: boolean; 
+/**
+ * 只查询在该时间戳(含)之后结束的记录，单位为秒
+ */
+since: number | null; 
+/**
+ * 只查询在该时间戳(含)之前结束的记录，单位为秒
+ */
+until: number | null; 
+/**
+ * 最多返回的记录数量
+ */
+limit: number | null }
 export type DownloadImageErrorEvent = DownloadImageErrorEventPayload
 export type DownloadImageErrorEventPayload = { id: number; url: string; errMsg: string }
 export type DownloadImageSuccessEvent = DownloadImageSuccessEventPayload
 export type DownloadImageSuccessEventPayload = { id: number; url: string; current: number }
+export type DownloadPauseEvent = DownloadPauseEventPayload
+export type DownloadPauseEventPayload = { id: number }
 export type DownloadPendingEvent = DownloadPendingEventPayload
 export type DownloadPendingEventPayload = { id: number; comicTitle: string; episodeTitle: string }
+export type DownloadResumeEvent = DownloadResumeEventPayload
+export type DownloadResumeEventPayload = { id: number }
 export type DownloadSpeedEvent = DownloadSpeedEventPayload
-export type DownloadSpeedEventPayload = { speed: string }
+export type DownloadSpeedEventPayload = { 
+/**
+ * 所有章节的下载速度，单位为字节/秒
+ */
+bytesPerSec: number; 
+/**
+ * 本次启动以来下载的总字节数
+ */
+totalBytes: number; 
+/**
+ * 所有下载中的章节预计还需要下载的字节数
+ */
+remainingBytes: number; 
+/**
+ * 所有下载中的章节预计还需要的秒数，无法估计时为 `None`
+ */
+etaSecs: number | null; episodes: EpisodeDownloadSpeed[] }
 export type DownloadStartEvent = DownloadStartEventPayload
-export type DownloadStartEventPayload = { id: number; total: number }
-export type EpisodeInfo = { episodeId: number; episodeTitle: string; comicId: number; comicTitle: string; isLocked: boolean; isDownloaded: boolean; comicInfo: ComicInfo }
+export type DownloadStartEventPayload = { id: number; total: number; 
+/**
+ * 开始下载前临时目录中已存在的有效图片数量
+ */
+downloaded: number }
+export type DownloadTask = { epInfo: EpisodeInfo; state: DownloadTaskState }
+export type DownloadTaskState = "Running" | "Paused" | "Cancelled"
+export type EpisodeDownloadSpeed = { id: number; 
+/**
+ * 该章节的下载速度，单位为字节/秒
+ */
+bytesPerSec: number; 
+/**
+ * 该章节本次下载的字节数
+ */
+downloadedBytes: number; 
+/**
+ * 该章节还需要下载的图片数量
+ */
+remainingPages: number; 
+/**
+ * 根据已下载图片的平均大小估计的剩余字节数
+ */
+remainingBytes: number; 
+/**
+ * 该章节预计还需要的秒数，无法估计时为 `None`
+ */
+etaSecs: number | null }
+/**
+ * 下载整部漫画时用于筛选章节的条件，未指定的条件不参与筛选
+ */
+export type EpisodeFilter = { 
+/**
+ * 只下载已解锁的章节
+ */
+unlockedOnly: boolean; 
+/**
+ * 只下载还没下载过的章节
+ */
+notDownloadedOnly: boolean; 
+/**
+ * 只下载免费的章节
+ */
+freeOnly: boolean; 
+/**
+ * 只下载 `ord` 大于等于该值的章节
+ */
+minOrd: number | null; 
+/**
+ * 只下载 `ord` 小于等于该值的章节
+ */
+maxOrd: number | null }
+export type EpisodeInfo = { episodeId: number; episodeTitle: string; comicId: number; comicTitle: string; isLocked: boolean; isDownloaded: boolean; ord: number; 
+/**
+ * 免费章节或限时免费中的章节
+ */
+isFree: boolean; 
+/**
+ * 漫画的竖版封面
+ */
+verticalCover: string; 
+/**
+ * 漫画是否从右往左阅读
+ */
+isRightToLeft: boolean; 
+/**
+ * 章节的封面
+ */
+episodeCover: string; comicInfo: ComicInfo }
 export type FavComicInfo = { has_fav_activity: boolean; fav_free_amount: number; fav_coupon_type: number }
 export type Increase = { days: number; increase_percent: number }
 export type InteractiveValue = { interact_value: string; is_jump: boolean; increase: Increase; percentile: number; description: string }
+/**
+ * 本地漫画库中的一部漫画
+ */
+export type LibraryComic = { 
+/**
+ * 漫画目录名，即过滤后的漫画标题
+ */
+comicTitle: string; comicDir: string; 
+/**
+ * 按名称自然排序的章节和单行本
+ */
+episodes: LibraryEpisode[]; 
+/**
+ * 所有章节和单行本的大小之和
+ */
+bytes: number }
+/**
+ * 本地漫画库中的一个章节或单行本
+ */
+export type LibraryEpisode = { 
+/**
+ * 章节名或单行本名，即去掉扩展名后的文件名
+ */
+name: string; path: string; archiveFormat: ArchiveFormat; 
+/**
+ * 实际包含的图片数量
+ */
+pageCount: number; 
+/**
+ * 文件或目录的大小
+ */
+bytes: number; 
+/**
+ * 从章节中读回的 `ComicInfo.xml`，pdf和epub中没有 `ComicInfo.xml`，此时为 `None`
+ */
+comicInfo: ComicInfo | null; 
+/**
+ * 是否不完整，下载中或下载失败残留的临时目录、图片数量少于 `ComicInfo.page_count` 以及无法读取的章节都是不完整的
+ */
+isIncomplete: boolean; 
+/**
+ * 读取章节失败时的错误链
+ */
+errMsg: string | null }
+/**
+ * 处理已下载的漫画时的范围
+ */
+export type LibraryScope = 
+/**
+ * 下载目录中的所有漫画
+ */
+"All" | 
+/**
+ * 下载目录中的某部漫画，值为漫画目录名，即过滤后的漫画标题
+ */
+{ Comic: string }
 export type NovelInSearchRespData = { novel_id: number; title: string; v_cover: string; finish_status: number; status: number; discount_type: number; numbers: number; style: StyleRespData; evaluate: string; author: string; tag: TagRespData }
 export type ProxyMode = "NoProxy" | "System" | "Custom"
 export type ReadScore = { read_score: string; is_jump: boolean; increase: Increase; percentile: number; description: string }
+export type RecordDownloadHistoryErrorEvent = RecordDownloadHistoryErrorEventPayload
+export type RecordDownloadHistoryErrorEventPayload = { errMsg: string }
 export type RemoveWatermarkEndEvent = RemoveWatermarkEndEventPayload
 export type RemoveWatermarkEndEventPayload = { dirPath: string }
 export type RemoveWatermarkErrorEvent = RemoveWatermarkErrorEventPayload
@@ -159,7 +626,11 @@ export type RemoveWatermarkStartEvent = RemoveWatermarkStartEventPayload
 export type RemoveWatermarkStartEventPayload = { dirPath: string; total: number }
 export type RemoveWatermarkSuccessEvent = RemoveWatermarkSuccessEventPayload
 export type RemoveWatermarkSuccessEventPayload = { dirPath: string; imgPath: string; current: number }
+export type RestoreDownloadTasksErrorEvent = RestoreDownloadTasksErrorEventPayload
+export type RestoreDownloadTasksErrorEventPayload = { errMsg: string }
 export type RookieFavTip = { is_show: boolean; used: number; total: number }
+export type SaveSeriesMetadataErrorEvent = SaveSeriesMetadataErrorEventPayload
+export type SaveSeriesMetadataErrorEventPayload = { comicTitle: string; errMsg: string }
 export type SearchComicRespData = { list: ComicInSearchRespData[]; total_page: number; total_num: number; similar: string; se_id: string; banner: BannerRespData }
 export type SearchNovelRespData = { total: number; list: NovelInSearchRespData[] }
 export type SearchRespData = { comic_data: SearchComicRespData; novel_data: SearchNovelRespData }
@@ -168,9 +639,55 @@ export type SetProxyErrorEventPayload = { errMsg: string }
 export type StoryElem = { id: number; name: string }
 export type StyleRespData = { id: number; name: string }
 export type Styles2 = { id: number; name: string }
+export type Subscription = { comicId: number; comicTitle: string; 
+/**
+ * 订阅时的时间戳，单位为秒
+ */
+subscribedAt: number; 
+/**
+ * 上次检查更新的时间戳，单位为秒，还没检查过则为 `None`
+ */
+lastCheckedAt: number | null; 
+/**
+ * 已知的章节id，只有不在其中的新章节才会被加入下载队列，为 `None` 时下次检查只记录已有的章节
+ */
+knownEpisodeIds: number[] | null }
+export type SubscriptionCheckEndEvent = SubscriptionCheckEndEventPayload
+export type SubscriptionCheckEndEventPayload = { comics: SubscriptionCheckResult[] }
+export type SubscriptionCheckResult = { comicId: number; comicTitle: string; 
+/**
+ * 本次检查加入下载队列的章节标题
+ */
+queuedEpisodeTitles: string[]; errMsg: string | null }
 export type Tag = { id: number; name: string }
 export type TagRespData = { id: number; name: string }
 export type UserProfileRespData = { mid: number; face: string; name: string }
+export type VerifyLibraryProgressEvent = VerifyLibraryProgressEventPayload
+export type VerifyLibraryProgressEventPayload = { comicTitle: string; episodeTitle: string; current: number; total: number }
+/**
+ * 将多个章节合并为单行本时的选项
+ */
+export type VolumeOptions = { 
+/**
+ * 卷号，会写入 `ComicInfo.xml` 的 `Volume`
+ */
+volume: number; 
+/**
+ * 要合并的章节id，为空时按 `min_ord` 和 `max_ord` 选择章节
+ */
+episodeIds: number[]; 
+/**
+ * 合并 `ord` 大于等于该值的章节
+ */
+minOrd: number | null; 
+/**
+ * 合并 `ord` 小于等于该值的章节
+ */
+maxOrd: number | null; 
+/**
+ * 单行本的格式，为 `None` 时使用配置中的下载格式
+ */
+archiveFormat: ArchiveFormat | null }
 export type WebQrcodeData = { base64: string; qrcodeKey: string }
 export type WebQrcodeStatusRespData = { url: string; refresh_token: string; timestamp: number; code: number; message: string }
 export type WikiRespData = { id: number; title: string; origin_title: string; vertical_cover: string; producer: string; author_name: string[]; publish_time: string; frequency: string }